@@ -0,0 +1,92 @@
+//! Per-item overhead of a five-stage `StreamExt` pipeline
+//!
+//! `spawned` reproduces the previous design, where every combinator spawned a
+//! tokio task and forwarded items through an unbounded channel. `adapters` is
+//! the current poll-based design. Both run the same map/filter/tap pipeline.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::StreamExt as _;
+use std::hint::black_box;
+use sugars_async_stream::{NotResult, StreamExt, TokioStream as AsyncStream};
+use tokio::sync::mpsc;
+
+const ITEMS: [u64; 2] = [1_000, 10_000];
+
+fn source(items: u64) -> AsyncStream<u64> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    for item in 0..items {
+        let _ = tx.send(item);
+    }
+    AsyncStream::new(rx)
+}
+
+/// One pipeline stage in the previous spawn-per-combinator style
+fn spawned_stage<T, U>(
+    stream: AsyncStream<T>,
+    mut f: impl FnMut(T) -> Option<U> + Send + 'static,
+) -> AsyncStream<U>
+where
+    T: Send + 'static + NotResult,
+    U: Send + 'static + NotResult,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut stream = stream;
+        while let Some(item) = stream.next().await {
+            if let Some(out) = f(item)
+                && tx.send(out).is_err()
+            {
+                break;
+            }
+        }
+    });
+    AsyncStream::new(rx)
+}
+
+async fn run_spawned(items: u64) -> usize {
+    let stream = spawned_stage(source(items), |n| Some(n + 1));
+    let stream = spawned_stage(stream, |n| (n % 3 != 0).then_some(n));
+    let stream = spawned_stage(stream, |n| Some(n * 2));
+    let stream = spawned_stage(stream, |n| {
+        black_box(&n);
+        Some(n)
+    });
+    let stream = spawned_stage(stream, |n| Some(n.to_string().len()));
+    stream.collect_async().await.len()
+}
+
+async fn run_adapters(items: u64) -> usize {
+    source(items)
+        .map_stream(|n| n + 1)
+        .filter_stream(|n| n % 3 != 0)
+        .map_stream(|n| n * 2)
+        .tap_each(|n| {
+            black_box(n);
+        })
+        .map_stream(|n| n.to_string().len())
+        .collect_async()
+        .await
+        .len()
+}
+
+fn pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()
+        .expect("tokio runtime");
+
+    let mut group = c.benchmark_group("five_stage_pipeline");
+    for items in ITEMS {
+        group.throughput(Throughput::Elements(items));
+        group.bench_with_input(BenchmarkId::new("spawned", items), &items, |b, &items| {
+            b.iter(|| runtime.block_on(run_spawned(items)))
+        });
+        group.bench_with_input(BenchmarkId::new("adapters", items), &items, |b, &items| {
+            b.iter(|| runtime.block_on(run_adapters(items)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -0,0 +1,536 @@
+//! Single-owner state machines driven by a mailbox
+//!
+//! An actor owns its state and handles one message at a time on its own
+//! task, so the state needs no locking. Callers talk to it through a
+//! cloneable [`ActorRef`]: [`tell`](ActorRef::tell) to fire and forget,
+//! [`ask`](ActorRef::ask) to get a reply back.
+//!
+//! ```ignore
+//! enum Counter {
+//!     Add(u64),
+//!     Get(Reply<u64>),
+//! }
+//!
+//! let counter = Actor::spawn(0u64, |total: &mut u64, msg| match msg {
+//!     Counter::Add(n) => *total += n,
+//!     Counter::Get(reply) => reply.send(*total),
+//! });
+//!
+//! counter.tell(Counter::Add(2))?;
+//! let total = counter.ask(Counter::Get).await;
+//! ```
+//!
+//! A handler that needs to await, e.g. on a client or a file, takes its
+//! state by value and hands it back from a future; see
+//! [`Actor::spawn_async`]. It still handles one message at a time.
+//!
+//! A handler that panics is supervised according to its [`Supervision`]:
+//! by default the state is rebuilt and the actor carries on with the next
+//! message. [`ActorRef::shutdown`] stops the actor gracefully once every
+//! message already in its mailbox has been handled.
+
+use crate::AsyncStream;
+use crate::result_types::AsyncResult;
+use crate::stream::{StreamBackend, StreamSender};
+use futures::FutureExt;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use sugars_async_task::{AsyncTask, NotResult};
+use tokio::sync::{oneshot, watch};
+
+/// Why an actor could not answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActorError {
+    /// The actor had stopped before the message reached it
+    Stopped,
+    /// The message was accepted but the handler dropped its [`Reply`],
+    /// e.g. because it panicked
+    NoReply,
+}
+
+impl fmt::Display for ActorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActorError::Stopped => write!(f, "actor has stopped"),
+            ActorError::NoReply => write!(f, "actor dropped the reply"),
+        }
+    }
+}
+
+impl std::error::Error for ActorError {}
+
+/// What happens when a handler panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supervision {
+    /// Stop the actor; queued messages are dropped
+    Stop,
+    /// Rebuild the state and keep going, unless the actor has already been
+    /// restarted `max_restarts` times within `within`
+    Restart {
+        /// Restarts allowed inside the window before the actor stops
+        max_restarts: u32,
+        /// Length of the sliding window
+        within: Duration,
+    },
+}
+
+impl Default for Supervision {
+    fn default() -> Self {
+        Supervision::Restart {
+            max_restarts: 3,
+            within: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Reply slot carried inside a message sent with [`ActorRef::ask`]
+pub struct Reply<R> {
+    sender: oneshot::Sender<R>,
+}
+
+impl<R> Reply<R> {
+    /// Answer the asking caller; a caller that gave up is ignored
+    pub fn send(self, value: R) {
+        let _ = self.sender.send(value);
+    }
+}
+
+impl<R> fmt::Debug for Reply<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reply")
+            .field("closed", &self.sender.is_closed())
+            .finish()
+    }
+}
+
+/// Item type of an actor's mailbox
+///
+/// Only named when picking a mailbox backend with
+/// [`ActorBuilder::spawn_on`].
+pub struct Envelope<M>(Letter<M>);
+
+enum Letter<M> {
+    Message(M),
+    Shutdown,
+}
+
+// Envelope is delivered through the mailbox stream
+impl<M> NotResult for Envelope<M> {}
+
+type Post<M> = Arc<dyn Fn(Envelope<M>) -> Result<(), Envelope<M>> + Send + Sync>;
+
+/// Handle for sending messages to an actor
+///
+/// Clones address the same actor. The actor also stops, after draining its
+/// mailbox, once every handle has been dropped.
+pub struct ActorRef<M> {
+    post: Post<M>,
+    /// Messages are posted under the read lock and the flag is cleared
+    /// under the write lock, so nothing is posted behind the shutdown
+    accepting: Arc<RwLock<bool>>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl<M> Clone for ActorRef<M> {
+    fn clone(&self) -> Self {
+        Self {
+            post: Arc::clone(&self.post),
+            accepting: Arc::clone(&self.accepting),
+            stopped: self.stopped.clone(),
+        }
+    }
+}
+
+impl<M> ActorRef<M>
+where
+    M: Send + 'static,
+{
+    /// Send a message without waiting for it to be handled
+    ///
+    /// Hands the message back if the actor is shutting down or has stopped.
+    pub fn tell(&self, message: M) -> Result<(), M> {
+        let accepting = read(&self.accepting);
+        if !*accepting {
+            return Err(message);
+        }
+        (self.post)(Envelope(Letter::Message(message))).map_err(|envelope| match envelope.0 {
+            Letter::Message(message) => message,
+            Letter::Shutdown => unreachable!("posted a message"),
+        })
+    }
+
+    /// Send a message carrying a [`Reply`] and wait for the answer
+    ///
+    /// ```ignore
+    /// let total = counter.ask(Counter::Get).await.into_ok();
+    /// ```
+    pub fn ask<R, F>(&self, message: F) -> AsyncTask<AsyncResult<R, ActorError>>
+    where
+        F: FnOnce(Reply<R>) -> M,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let sent = self.tell(message(Reply { sender }));
+        AsyncTask::from_future(async move {
+            if sent.is_err() {
+                return AsyncResult::err(ActorError::Stopped);
+            }
+            match receiver.await {
+                Ok(reply) => AsyncResult::ok(reply),
+                Err(_) => AsyncResult::err(ActorError::NoReply),
+            }
+        })
+    }
+
+    /// Stop accepting messages and resolve once the actor has handled
+    /// everything already in its mailbox
+    pub fn shutdown(&self) -> AsyncTask<()> {
+        {
+            let mut accepting = write(&self.accepting);
+            if std::mem::replace(&mut *accepting, false) {
+                // The actor may already have stopped on its own
+                let _ = (self.post)(Envelope(Letter::Shutdown));
+            }
+        }
+        let mut stopped = self.stopped.clone();
+        AsyncTask::from_future(async move {
+            // An error means the actor task is gone, which is stopped too
+            let _ = stopped.wait_for(|stopped| *stopped).await;
+        })
+    }
+
+    /// Returns true until the actor has stopped
+    pub fn is_alive(&self) -> bool {
+        !*self.stopped.borrow()
+    }
+}
+
+impl<M> fmt::Debug for ActorRef<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorRef")
+            .field("accepting", &*read(&self.accepting))
+            .field("stopped", &*self.stopped.borrow())
+            .finish()
+    }
+}
+
+fn read(lock: &RwLock<bool>) -> std::sync::RwLockReadGuard<'_, bool> {
+    lock.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write(lock: &RwLock<bool>) -> std::sync::RwLockWriteGuard<'_, bool> {
+    lock.write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Entry points for spawning actors
+pub struct Actor;
+
+impl Actor {
+    /// Spawn an actor on the default backend with default supervision
+    ///
+    /// After a panic the actor restarts from a clone of `state`.
+    #[track_caller]
+    pub fn spawn<S, M, H>(state: S, handler: H) -> ActorRef<M>
+    where
+        S: Clone + Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(&mut S, M) + Send + 'static,
+    {
+        Self::build(move || state.clone()).spawn(handler)
+    }
+
+    /// Spawn an actor whose handler awaits, on the default backend
+    ///
+    /// The handler takes the state and returns a future resolving to the
+    /// next state; the next message waits for it:
+    ///
+    /// ```ignore
+    /// let saver = Actor::spawn_async(Saved::default(), |mut saved, Save(doc)| async move {
+    ///     store.write(&doc).await;
+    ///     saved.count += 1;
+    ///     saved
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn spawn_async<S, M, H, F>(state: S, handler: H) -> ActorRef<M>
+    where
+        S: Clone + Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(S, M) -> F + Send + 'static,
+        F: Future<Output = S> + Send + 'static,
+    {
+        Self::build(move || state.clone()).spawn_async(handler)
+    }
+
+    /// Configure an actor whose state is created, and re-created after a
+    /// restart, by `init`
+    pub fn build<S, I>(init: I) -> ActorBuilder<I>
+    where
+        I: FnMut() -> S + Send + 'static,
+    {
+        ActorBuilder {
+            init,
+            supervision: Supervision::default(),
+        }
+    }
+}
+
+/// Builder returned by [`Actor::build`]
+pub struct ActorBuilder<I> {
+    init: I,
+    supervision: Supervision,
+}
+
+impl<I> ActorBuilder<I> {
+    /// Choose what happens when the handler panics
+    pub fn supervision(mut self, supervision: Supervision) -> Self {
+        self.supervision = supervision;
+        self
+    }
+
+    /// Spawn the actor on the default backend
+    #[track_caller]
+    pub fn spawn<S, M, H>(self, handler: H) -> ActorRef<M>
+    where
+        I: FnMut() -> S + Send + 'static,
+        S: Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(&mut S, M) + Send + 'static,
+    {
+        self.spawn_on::<AsyncStream<Envelope<M>>, S, M, H>(handler)
+    }
+
+    /// Spawn the actor with its mailbox on backend `B`
+    ///
+    /// ```ignore
+    /// let actor = Actor::build(State::default)
+    ///     .spawn_on::<CrossbeamStream<Envelope<Msg>>, _, _, _>(handle);
+    /// ```
+    #[track_caller]
+    pub fn spawn_on<B, S, M, H>(self, mut handler: H) -> ActorRef<M>
+    where
+        B: StreamBackend<Envelope<M>>,
+        B::Sender: Sync,
+        I: FnMut() -> S + Send + 'static,
+        S: Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(&mut S, M) + Send + 'static,
+    {
+        self.spawn_async_on::<B, S, M, _, _>(move |mut state, message| {
+            handler(&mut state, message);
+            std::future::ready(state)
+        })
+    }
+
+    /// Spawn an actor whose handler awaits, on the default backend; see
+    /// [`Actor::spawn_async`]
+    #[track_caller]
+    pub fn spawn_async<S, M, H, F>(self, handler: H) -> ActorRef<M>
+    where
+        I: FnMut() -> S + Send + 'static,
+        S: Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(S, M) -> F + Send + 'static,
+        F: Future<Output = S> + Send + 'static,
+    {
+        self.spawn_async_on::<AsyncStream<Envelope<M>>, S, M, H, F>(handler)
+    }
+
+    /// Spawn an actor whose handler awaits, with its mailbox on backend `B`
+    #[track_caller]
+    pub fn spawn_async_on<B, S, M, H, F>(self, mut handler: H) -> ActorRef<M>
+    where
+        B: StreamBackend<Envelope<M>>,
+        B::Sender: Sync,
+        I: FnMut() -> S + Send + 'static,
+        S: Send + 'static,
+        M: NotResult + Send + 'static,
+        H: FnMut(S, M) -> F + Send + 'static,
+        F: Future<Output = S> + Send + 'static,
+    {
+        let (sender, mut mailbox) = B::channel();
+        let (stopped_tx, stopped) = watch::channel(false);
+        let accepting = Arc::new(RwLock::new(true));
+        let ActorBuilder {
+            mut init,
+            supervision,
+        } = self;
+
+        let closed = Arc::clone(&accepting);
+        B::spawn(async move {
+            let mut state = init();
+            let mut restarts = VecDeque::new();
+            while let Some(envelope) = futures::StreamExt::next(&mut mailbox).await {
+                let message = match envelope.0 {
+                    Letter::Message(message) => message,
+                    Letter::Shutdown => break,
+                };
+                let handled = match catch_unwind(AssertUnwindSafe(|| handler(state, message))) {
+                    Ok(next) => AssertUnwindSafe(next).catch_unwind().await,
+                    Err(panic) => Err(panic),
+                };
+                if let Ok(next) = handled {
+                    state = next;
+                    continue;
+                }
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?supervision, "actor handler panicked");
+                match supervision {
+                    Supervision::Restart {
+                        max_restarts,
+                        within,
+                    } => {
+                        let now = Instant::now();
+                        restarts.retain(|at: &Instant| now.duration_since(*at) < within);
+                        if restarts.len() >= max_restarts as usize {
+                            break;
+                        }
+                        restarts.push_back(now);
+                        state = init();
+                    }
+                    Supervision::Stop => break,
+                }
+            }
+            *write(&closed) = false;
+            // Drop queued messages (and their replies) before announcing
+            drop(mailbox);
+            let _ = stopped_tx.send(true);
+        });
+
+        ActorRef {
+            post: Arc::new(move |envelope| sender.send_item(envelope)),
+            accepting,
+            stopped,
+        }
+    }
+}
+
+impl<I> fmt::Debug for ActorBuilder<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorBuilder")
+            .field("supervision", &self.supervision)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "tokio-backend"))]
+mod tests {
+    use super::*;
+
+    enum Counter {
+        Add(u64),
+        Boom,
+        Get(Reply<u64>),
+    }
+
+    fn counter(supervision: Supervision) -> ActorRef<Counter> {
+        Actor::build(|| 0u64)
+            .supervision(supervision)
+            .spawn(|total: &mut u64, message| match message {
+                Counter::Add(n) => *total += n,
+                Counter::Boom => panic!("boom"),
+                Counter::Get(reply) => reply.send(*total),
+            })
+    }
+
+    #[tokio::test]
+    async fn ask_sees_earlier_tells() {
+        let actor = counter(Supervision::default());
+        actor.tell(Counter::Add(2)).ok();
+        actor.tell(Counter::Add(3)).ok();
+        assert_eq!(actor.ask(Counter::Get).await.into_ok(), Some(5));
+    }
+
+    #[tokio::test]
+    async fn panics_restart_or_stop_the_actor() {
+        let actor = counter(Supervision::default());
+        actor.tell(Counter::Add(2)).ok();
+        actor.tell(Counter::Boom).ok();
+        assert_eq!(actor.ask(Counter::Get).await.into_ok(), Some(0));
+
+        let actor = counter(Supervision::Stop);
+        actor.tell(Counter::Boom).ok();
+        let answer = actor.ask(Counter::Get).await.into_err();
+        assert!(matches!(
+            answer,
+            Some(ActorError::NoReply | ActorError::Stopped)
+        ));
+        actor.shutdown().await;
+        assert!(!actor.is_alive());
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_the_mailbox() {
+        let actor = counter(Supervision::default());
+        for _ in 0..100 {
+            actor.tell(Counter::Add(1)).ok();
+        }
+        let pending = actor.ask(Counter::Get);
+        actor.shutdown().await;
+
+        assert_eq!(pending.await.into_ok(), Some(100));
+        assert!(actor.tell(Counter::Add(1)).is_err());
+        assert_eq!(
+            actor.ask(Counter::Get).await.into_err(),
+            Some(ActorError::Stopped)
+        );
+    }
+
+    #[tokio::test]
+    async fn async_handlers_await_between_messages() {
+        let log = Actor::spawn_async(Vec::new(), |mut lines: Vec<String>, message| async move {
+            match message {
+                Log::Line(line) => {
+                    tokio::task::yield_now().await;
+                    lines.push(line);
+                }
+                Log::Lines(reply) => reply.send(lines.clone()),
+            }
+            lines
+        });
+        log.tell(Log::Line("a".into())).ok();
+        log.tell(Log::Line("b".into())).ok();
+        assert_eq!(
+            log.ask(Log::Lines).await.into_ok(),
+            Some(vec!["a".into(), "b".into()])
+        );
+    }
+
+    #[tokio::test]
+    async fn accepted_tells_are_handled_before_shutdown() {
+        let handled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&handled);
+        let actor = Actor::spawn((), move |_, _: u32| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        let teller = actor.clone();
+        let (started, start) = std::sync::mpsc::channel();
+        let racing = std::thread::spawn(move || {
+            let mut accepted = 0;
+            while teller.tell(accepted).is_ok() {
+                accepted += 1;
+                if accepted == 1 {
+                    let _ = started.send(());
+                }
+            }
+            accepted
+        });
+        start.recv().unwrap();
+        actor.shutdown().await;
+        let accepted = racing.join().unwrap();
+        assert_eq!(
+            handled.load(std::sync::atomic::Ordering::SeqCst),
+            accepted as usize
+        );
+    }
+
+    enum Log {
+        Line(String),
+        Lines(Reply<Vec<String>>),
+    }
+}
@@ -0,0 +1,511 @@
+//! Poll-based stream adapters behind the `StreamExt` combinators
+//!
+//! Each adapter wraps its upstream and does its work inside `poll_next`, so a
+//! pipeline of N combinators is a single nested stream polled by the consumer:
+//! no spawned tasks and no intermediate channels.
+
+use futures::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use sugars_async_task::{Acquire, Limiter, RateLimiter};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Stream adapter for `StreamExt::map_stream`
+pub struct Map<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> Map<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for Map<S, F> {}
+
+impl<S, F, U> Stream for Map<S, F>
+where
+    S: Stream + Unpin,
+    F: FnMut(S::Item) -> U,
+{
+    type Item = U;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<U>> {
+        let this = self.get_mut();
+        let item = ready!(Pin::new(&mut this.stream).poll_next(cx));
+        Poll::Ready(item.map(&mut this.f))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Stream adapter for `StreamExt::filter_stream`
+pub struct Filter<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> Filter<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for Filter<S, F> {}
+
+impl<S, F> Stream for Filter<S, F>
+where
+    S: Stream + Unpin,
+    F: FnMut(&S::Item) -> bool,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(item) if (this.f)(&item) => return Poll::Ready(Some(item)),
+                Some(_) => continue,
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.stream.size_hint().1)
+    }
+}
+
+/// Stream adapter for `StreamExt::tap_each`
+pub struct TapEach<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> TapEach<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for TapEach<S, F> {}
+
+impl<S, F> Stream for TapEach<S, F>
+where
+    S: Stream + Unpin,
+    F: FnMut(&S::Item),
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let item = ready!(Pin::new(&mut this.stream).poll_next(cx));
+        if let Some(item) = &item {
+            (this.f)(item);
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Stream adapter for `StreamExt::tee_each`
+pub struct TeeEach<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> TeeEach<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for TeeEach<S, F> {}
+
+impl<S, F> Stream for TeeEach<S, F>
+where
+    S: Stream + Unpin,
+    S::Item: Clone,
+    F: FnMut(S::Item),
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let item = ready!(Pin::new(&mut this.stream).poll_next(cx));
+        if let Some(item) = &item {
+            (this.f)(item.clone());
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Stream adapter for `StreamExt::on_result`
+///
+/// Ends the stream the first time the handler returns an error.
+pub struct OnResult<S, F> {
+    stream: Option<S>,
+    f: F,
+}
+
+impl<S, F> OnResult<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self {
+            stream: Some(stream),
+            f,
+        }
+    }
+}
+
+impl<S: Unpin, F> Unpin for OnResult<S, F> {}
+
+impl<S, F> Stream for OnResult<S, F>
+where
+    S: Stream + Unpin,
+    F: FnMut(Result<S::Item, Error>) -> Result<S::Item, Error>,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let Some(stream) = this.stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match ready!(Pin::new(stream).poll_next(cx)) {
+            Some(item) => match (this.f)(Ok(item)) {
+                Ok(item) => Poll::Ready(Some(item)),
+                Err(_) => {
+                    this.stream = None;
+                    Poll::Ready(None)
+                }
+            },
+            None => {
+                this.stream = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+/// Stream adapter for `StreamExt::on_chunk`
+pub struct OnChunk<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> OnChunk<S, F> {
+    pub(crate) fn new(stream: S, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for OnChunk<S, F> {}
+
+impl<S, F, U> Stream for OnChunk<S, F>
+where
+    S: Stream + Unpin,
+    F: FnMut(Result<S::Item, Error>) -> U,
+{
+    type Item = U;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<U>> {
+        let this = self.get_mut();
+        let item = ready!(Pin::new(&mut this.stream).poll_next(cx));
+        Poll::Ready(item.map(|item| (this.f)(Ok(item))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Stream adapter for `StreamExt::partition_chunks`
+pub struct PartitionChunks<S: Stream> {
+    stream: Option<S>,
+    buffer: Vec<S::Item>,
+    chunk_size: usize,
+}
+
+impl<S: Stream> PartitionChunks<S> {
+    pub(crate) fn new(stream: S, chunk_size: usize) -> Self {
+        let chunk_size = chunk_size.max(1);
+        Self {
+            stream: Some(stream),
+            buffer: Vec::with_capacity(chunk_size),
+            chunk_size,
+        }
+    }
+}
+
+impl<S: Stream + Unpin> Unpin for PartitionChunks<S> {}
+
+impl<S> Stream for PartitionChunks<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = Vec<S::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while let Some(stream) = this.stream.as_mut() {
+            match ready!(Pin::new(stream).poll_next(cx)) {
+                Some(item) => {
+                    this.buffer.push(item);
+                    if this.buffer.len() >= this.chunk_size {
+                        let chunk = std::mem::replace(
+                            &mut this.buffer,
+                            Vec::with_capacity(this.chunk_size),
+                        );
+                        return Poll::Ready(Some(chunk));
+                    }
+                }
+                None => this.stream = None,
+            }
+        }
+
+        // Flush remaining items once the upstream has ended
+        if this.buffer.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(std::mem::take(&mut this.buffer)))
+        }
+    }
+}
+
+/// Stream adapter for `StreamExt::rate_limit`
+///
+/// Each item is held back until the limiter grants a permit, so the upstream
+/// is never polled ahead of the limit.
+pub struct RateLimit<S: Stream> {
+    stream: S,
+    limiter: RateLimiter,
+    pending: Option<(S::Item, Acquire<()>)>,
+}
+
+impl<S: Stream> RateLimit<S> {
+    pub(crate) fn new(stream: S, limiter: RateLimiter) -> Self {
+        Self {
+            stream,
+            limiter,
+            pending: None,
+        }
+    }
+}
+
+impl<S: Stream + Unpin> Unpin for RateLimit<S> {}
+
+impl<S> Stream for RateLimit<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        if this.pending.is_none() {
+            match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(item) => this.pending = Some((item, this.limiter.acquire())),
+                None => return Poll::Ready(None),
+            }
+        }
+        if let Some((_, permit)) = this.pending.as_mut() {
+            ready!(permit.as_mut().poll(cx));
+        }
+        Poll::Ready(this.pending.take().map(|(item, _)| item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = usize::from(self.pending.is_some());
+        let (lower, upper) = self.stream.size_hint();
+        (
+            lower.saturating_add(pending),
+            upper.and_then(|upper| upper.checked_add(pending)),
+        )
+    }
+}
+
+/// Stream adapter for `StreamExt::named`
+///
+/// Polls the upstream inside an `async_stream` span and records the item
+/// count and duration once the stream closes or is dropped.
+#[cfg(feature = "tracing")]
+pub struct Traced<S> {
+    stream: S,
+    span: tracing::Span,
+    items: u64,
+    started: std::time::Instant,
+    finished: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl<S> Traced<S> {
+    pub(crate) fn new(
+        stream: S,
+        name: &str,
+        location: &'static std::panic::Location<'static>,
+    ) -> Self {
+        let span = tracing::debug_span!(
+            "async_stream",
+            stream.name = name,
+            caller = %location,
+            items = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        );
+        Self {
+            stream,
+            span,
+            items: 0,
+            started: std::time::Instant::now(),
+            finished: false,
+        }
+    }
+
+    fn record_totals(&self) {
+        self.span.record("items", self.items);
+        self.span
+            .record("duration_ms", self.started.elapsed().as_millis() as u64);
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S> Stream for Traced<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let _entered = this.span.enter();
+        match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+            Some(item) => {
+                this.items += 1;
+                Poll::Ready(Some(item))
+            }
+            None => {
+                if !this.finished {
+                    this.finished = true;
+                    this.record_totals();
+                    tracing::debug!(items = this.items, "stream closed");
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S> Drop for Traced<S> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _entered = self.span.enter();
+            self.record_totals();
+            tracing::debug!(items = self.items, "stream dropped before completion");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AsyncResult, AsyncResultChunk, AsyncStream, StreamBackend, StreamExt, StreamSender,
+    };
+
+    fn stream_of(items: Vec<u32>) -> AsyncStream<u32> {
+        let (tx, stream): (_, AsyncStream<u32>) = StreamBackend::channel();
+        for item in items {
+            let _ = tx.send_item(item);
+        }
+        stream
+    }
+
+    #[tokio::test]
+    async fn pipeline_runs_without_clone() {
+        struct NoClone(u32);
+
+        let items = stream_of(vec![1, 2, 3, 4, 5])
+            .map_stream(NoClone)
+            .filter_stream(|n| n.0 % 2 == 1)
+            .map_stream(|n| n.0 * 10)
+            .collect_async()
+            .await;
+        assert_eq!(items, vec![10, 30, 50]);
+    }
+
+    #[tokio::test]
+    async fn partition_chunks_flushes_remainder() {
+        let chunks = stream_of(vec![1, 2, 3, 4, 5])
+            .partition_chunks(2)
+            .collect_async()
+            .await;
+        assert_eq!(chunks, vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[tokio::test]
+    async fn on_result_stops_at_first_error() {
+        let items = stream_of(vec![1, 2, 3, 4])
+            .on_result(|item| match item {
+                Ok(3) => Err("three".into()),
+                other => other,
+            })
+            .collect_async()
+            .await;
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn try_collect_stops_at_first_error_chunk() {
+        let result = stream_of(vec![1, 2, 3, 4])
+            .map_stream(|n| match n {
+                3 => AsyncResultChunk::err(format!("bad {n}")),
+                n => AsyncResultChunk::ok(n),
+            })
+            .try_collect()
+            .await;
+        assert_eq!(result, AsyncResult::err("bad 3".to_string()));
+
+        let result = stream_of(vec![1, 2])
+            .map_stream(AsyncResultChunk::<u32, String>::ok)
+            .try_collect()
+            .await;
+        assert_eq!(result.into_ok(), Some(vec![1, 2]));
+    }
+
+    #[tokio::test]
+    async fn named_stream_passes_items_through() {
+        let items = stream_of(vec![1, 2, 3])
+            .named("numbers")
+            .map_stream(|n| n + 1)
+            .collect_async()
+            .await;
+        assert_eq!(items, vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_spaces_items_out() {
+        // Real time, so the wait also works on the std and crossbeam producers
+        let limiter = sugars_async_task::RateLimiter::per_second(50).with_burst(1);
+        let started = std::time::Instant::now();
+        let items = stream_of(vec![1, 2, 3])
+            .rate_limit(&limiter)
+            .collect_async()
+            .await;
+        assert_eq!(items, vec![1, 2, 3]);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,501 @@
+//! Circuit breaker for calls to flaky dependencies
+//!
+//! A [`CircuitBreaker`] counts consecutive failures of the calls it wraps.
+//! Once they reach the threshold the circuit opens and further calls fail
+//! immediately with [`CircuitOpen`] instead of reaching the dependency. After
+//! the reset timeout a limited number of trial calls are let through
+//! (half-open): enough successes close the circuit again, a failure re-opens
+//! it.
+//!
+//! ```ignore
+//! let breaker = CircuitBreaker::new()
+//!     .failure_threshold(3)
+//!     .reset_timeout(Duration::from_secs(30));
+//!
+//! let result = breaker.call(|| client.call_tool("search", args)).await;
+//! ```
+//!
+//! Timeouts are measured on the Tokio clock.
+
+use crate::AsyncStream;
+use crate::result_types::AsyncResult;
+use crate::stream::StreamBackend;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use sugars_async_task::{AsyncTask, NotResult};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+/// Events buffered per subscriber before the oldest are dropped
+const EVENT_CAPACITY: usize = 64;
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through; failures are counted
+    Closed,
+    /// Calls are rejected until the reset timeout elapses
+    Open,
+    /// A limited number of trial calls decide whether to close again
+    HalfOpen,
+}
+
+/// A transition between two [`CircuitState`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitEvent {
+    /// State before the transition
+    pub from: CircuitState,
+    /// State after the transition
+    pub to: CircuitState,
+    /// Consecutive failures counted when the transition happened
+    pub failures: u32,
+}
+
+/// A call was rejected because the circuit is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitOpen {
+    /// Time until the circuit lets a trial call through
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "circuit open, retry in {:?}", self.retry_after)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Error returned by [`CircuitBreaker::call`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError<E> {
+    /// The call was short-circuited and never ran
+    Open(CircuitOpen),
+    /// The call ran and failed
+    Failed(E),
+}
+
+impl<E> CircuitError<E> {
+    /// The call's own error, if it ran
+    pub fn into_failed(self) -> Option<E> {
+        match self {
+            CircuitError::Failed(error) => Some(error),
+            CircuitError::Open(_) => None,
+        }
+    }
+}
+
+impl<E> From<CircuitOpen> for CircuitError<E> {
+    fn from(open: CircuitOpen) -> Self {
+        CircuitError::Open(open)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open(open) => open.fmt(f),
+            CircuitError::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for CircuitError<E> {}
+
+/// Thresholds of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    failure_threshold: u32,
+    success_threshold: u32,
+    half_open_calls: u32,
+    reset_timeout: Duration,
+}
+
+/// Mutable state behind the breaker's lock
+struct Machine {
+    state: CircuitState,
+    failures: u32,
+    successes: u32,
+    in_flight: u32,
+    opened_at: Instant,
+    /// Bumped on every transition; a call only counts towards the state
+    /// it was admitted in
+    generation: u64,
+}
+
+struct Inner {
+    settings: Mutex<Settings>,
+    machine: Mutex<Machine>,
+    events: broadcast::Sender<CircuitEvent>,
+}
+
+/// Shared circuit breaker; clones guard the same dependency
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Inner>,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker that opens after 5 consecutive failures and tries
+    /// again after 30 seconds
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            inner: Arc::new(Inner {
+                settings: Mutex::new(Settings {
+                    failure_threshold: 5,
+                    success_threshold: 1,
+                    half_open_calls: 1,
+                    reset_timeout: Duration::from_secs(30),
+                }),
+                machine: Mutex::new(Machine {
+                    state: CircuitState::Closed,
+                    failures: 0,
+                    successes: 0,
+                    in_flight: 0,
+                    opened_at: Instant::now(),
+                    generation: 0,
+                }),
+                events,
+            }),
+        }
+    }
+
+    /// Consecutive failures that open the circuit (at least 1)
+    pub fn failure_threshold(self, failures: u32) -> Self {
+        self.settings().failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Successful trial calls needed to close the circuit again (at least 1)
+    pub fn success_threshold(self, successes: u32) -> Self {
+        self.settings().success_threshold = successes.max(1);
+        self
+    }
+
+    /// Trial calls allowed at once while half-open (at least 1)
+    pub fn half_open_calls(self, calls: u32) -> Self {
+        self.settings().half_open_calls = calls.max(1);
+        self
+    }
+
+    /// How long the circuit stays open before trial calls are let through
+    pub fn reset_timeout(self, timeout: Duration) -> Self {
+        self.settings().reset_timeout = timeout;
+        self
+    }
+
+    /// Current state
+    ///
+    /// An open circuit whose reset timeout has elapsed reports half-open.
+    pub fn state(&self) -> CircuitState {
+        let settings = *self.settings();
+        let mut machine = self.machine();
+        self.expire(&mut machine, &settings);
+        machine.state
+    }
+
+    /// Run `call` through the breaker
+    ///
+    /// `call` is only invoked if the circuit lets it through; otherwise the
+    /// returned task resolves to [`CircuitError::Open`] straight away.
+    #[track_caller]
+    pub fn call<F, T, E>(&self, call: F) -> AsyncTask<AsyncResult<T, CircuitError<E>>>
+    where
+        F: FnOnce() -> AsyncTask<AsyncResult<T, E>>,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let generation = match self.admit() {
+            Ok(generation) => generation,
+            Err(open) => return AsyncTask::from_value(AsyncResult::err(open.into())),
+        };
+
+        // Built first so a panicking `call` still gives its slot back
+        let mut guard = CallGuard {
+            breaker: self.clone(),
+            generation,
+            finished: false,
+        };
+        let task = call();
+        AsyncTask::from_future(async move {
+            let result = task.await;
+            guard.finished = true;
+            guard.breaker.record(guard.generation, result.is_ok());
+            result.map_err(CircuitError::Failed)
+        })
+    }
+
+    /// Stream of state transitions from now on
+    ///
+    /// Ends once every clone of the breaker has been dropped. A subscriber
+    /// that falls more than 64 events behind skips the oldest ones.
+    pub fn events(&self) -> AsyncStream<CircuitEvent> {
+        self.events_on()
+    }
+
+    /// Stream of state transitions on a specific backend
+    pub fn events_on<S: StreamBackend<CircuitEvent>>(&self) -> S {
+        let receiver = self.inner.events.subscribe();
+        S::from_adapter(futures::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => return Some((event, receiver)),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Let a call through, returning the generation it was admitted in, or
+    /// reject it
+    fn admit(&self) -> Result<u64, CircuitOpen> {
+        let settings = *self.settings();
+        let mut machine = self.machine();
+        self.expire(&mut machine, &settings);
+        match machine.state {
+            CircuitState::Closed => Ok(machine.generation),
+            CircuitState::Open => Err(CircuitOpen {
+                retry_after: (machine.opened_at + settings.reset_timeout)
+                    .saturating_duration_since(Instant::now()),
+            }),
+            CircuitState::HalfOpen if machine.in_flight < settings.half_open_calls => {
+                machine.in_flight += 1;
+                Ok(machine.generation)
+            }
+            CircuitState::HalfOpen => Err(CircuitOpen {
+                retry_after: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Record the outcome of a call admitted in `generation`
+    fn record(&self, generation: u64, success: bool) {
+        let settings = *self.settings();
+        let mut machine = self.machine();
+        // Admitted before the last transition, e.g. a slow call started
+        // while closed that ends during a half-open trial: it says nothing
+        // about the current state
+        if machine.generation != generation {
+            return;
+        }
+        match (machine.state, success) {
+            (CircuitState::HalfOpen, true) => {
+                machine.in_flight = machine.in_flight.saturating_sub(1);
+                machine.successes += 1;
+                if machine.successes >= settings.success_threshold {
+                    machine.failures = 0;
+                    self.transition(&mut machine, CircuitState::Closed);
+                }
+            }
+            (CircuitState::HalfOpen, false) => {
+                machine.failures += 1;
+                self.open(&mut machine);
+            }
+            (CircuitState::Closed, true) => machine.failures = 0,
+            (CircuitState::Closed, false) => {
+                machine.failures += 1;
+                if machine.failures >= settings.failure_threshold {
+                    self.open(&mut machine);
+                }
+            }
+            // Nothing is admitted while open
+            (CircuitState::Open, _) => {}
+        }
+    }
+
+    /// Move an open circuit to half-open once its timeout has elapsed
+    fn expire(&self, machine: &mut Machine, settings: &Settings) {
+        if machine.state == CircuitState::Open
+            && machine.opened_at.elapsed() >= settings.reset_timeout
+        {
+            machine.successes = 0;
+            machine.in_flight = 0;
+            self.transition(machine, CircuitState::HalfOpen);
+        }
+    }
+
+    fn open(&self, machine: &mut Machine) {
+        machine.opened_at = Instant::now();
+        machine.in_flight = 0;
+        self.transition(machine, CircuitState::Open);
+    }
+
+    fn transition(&self, machine: &mut Machine, to: CircuitState) {
+        let from = std::mem::replace(&mut machine.state, to);
+        machine.generation += 1;
+        // No subscribers is fine
+        let _ = self.inner.events.send(CircuitEvent {
+            from,
+            to,
+            failures: machine.failures,
+        });
+    }
+
+    fn settings(&self) -> MutexGuard<'_, Settings> {
+        self.inner
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn machine(&self) -> MutexGuard<'_, Machine> {
+        self.inner
+            .machine
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let machine = self.machine();
+        f.debug_struct("CircuitBreaker")
+            .field("state", &machine.state)
+            .field("failures", &machine.failures)
+            .finish()
+    }
+}
+
+/// Counts a call as failed if it never reports back, e.g. because the
+/// wrapped task or the closure creating it panicked
+struct CallGuard {
+    breaker: CircuitBreaker,
+    generation: u64,
+    finished: bool,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.breaker.record(self.generation, false);
+        }
+    }
+}
+
+// CircuitEvent is delivered through AsyncStream
+impl NotResult for CircuitEvent {}
+
+#[cfg(all(test, feature = "tokio-backend"))]
+mod tests {
+    use super::*;
+    use crate::StreamExt;
+
+    fn outcome(ok: bool) -> AsyncTask<AsyncResult<u32, &'static str>> {
+        AsyncTask::from_future(async move {
+            if ok {
+                AsyncResult::ok(1)
+            } else {
+                AsyncResult::err("down")
+            }
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_after_threshold_and_recovers_after_timeout() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(2)
+            .reset_timeout(Duration::from_secs(10));
+        let events = breaker.events();
+
+        for _ in 0..2 {
+            assert!(breaker.call(|| outcome(false)).await.is_err());
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let rejected = breaker
+            .call(|| -> AsyncTask<AsyncResult<u32, &str>> { panic!("must not be called") })
+            .await;
+        assert_eq!(
+            rejected.into_err(),
+            Some(CircuitError::Open(CircuitOpen {
+                retry_after: Duration::from_secs(10)
+            }))
+        );
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(breaker.call(|| outcome(true)).await.into_ok(), Some(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        drop(breaker);
+        let transitions: Vec<_> = events
+            .collect()
+            .await
+            .into_iter()
+            .map(|event| (event.from, event.to))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (CircuitState::Closed, CircuitState::Open),
+                (CircuitState::Open, CircuitState::HalfOpen),
+                (CircuitState::HalfOpen, CircuitState::Closed),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_trial_reopens_the_circuit() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .reset_timeout(Duration::from_secs(5));
+
+        let _ = breaker.call(|| outcome(false)).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let _ = breaker.call(|| outcome(false)).await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn calls_from_an_earlier_state_do_not_decide_a_trial() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .reset_timeout(Duration::from_secs(5));
+
+        let slow = breaker.call(|| {
+            AsyncTask::from_future(async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                AsyncResult::<u32, &str>::ok(1)
+            })
+        });
+        let _ = breaker.call(|| outcome(false)).await;
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // Admitted while closed, so its success must not close the circuit
+        assert_eq!(slow.await.into_ok(), Some(1));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panicking_call_gives_back_its_trial_slot() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .reset_timeout(Duration::from_secs(5));
+
+        let _ = breaker.call(|| outcome(false)).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            breaker.call(|| -> AsyncTask<AsyncResult<u32, &str>> { panic!("bad request") })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(breaker.call(|| outcome(true)).await.into_ok(), Some(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
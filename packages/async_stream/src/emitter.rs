@@ -0,0 +1,353 @@
+//! Incremental emitter with per-item error routing
+//!
+//! Unlike [`EmitterBuilder`](crate::EmitterBuilder), which waits for a whole
+//! batch before streaming anything, a [`StreamEmitter`] hands the producer an
+//! [`Emitter`] that pushes each item to the consumer as soon as it exists.
+//! Recoverable errors are routed one at a time through an optional handler and
+//! collected into the [`StreamSummary`] that resolves once the producer ends.
+
+use crate::AsyncStream;
+use crate::stream::{StreamBackend, StreamSender};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use sugars_async_task::{AsyncTask, NotResult};
+use sugars_collections::ZeroOneOrMany;
+
+/// Untyped error used when a producer does not pick its own error type
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What the stream should do after a recoverable error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Record the error and keep streaming
+    Continue,
+    /// Record the error and stop accepting items
+    Stop,
+}
+
+/// How a producer finished
+#[derive(Debug)]
+pub enum StreamOutcome<E> {
+    /// The producer ran to completion
+    Completed,
+    /// The error handler returned [`ErrorAction::Stop`]
+    Stopped,
+    /// The consumer dropped the stream before the producer finished
+    Closed,
+    /// The producer returned a fatal error
+    Failed(E),
+}
+
+/// Final report of an emitter run
+#[derive(Debug)]
+pub struct StreamSummary<E> {
+    /// Number of items delivered to the stream
+    pub emitted: usize,
+    /// Recoverable errors, in the order they were reported
+    pub errors: Vec<E>,
+    /// How the producer finished
+    pub outcome: StreamOutcome<E>,
+}
+
+impl<E> StreamSummary<E> {
+    /// Returns true if the producer completed without any errors
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && matches!(self.outcome, StreamOutcome::Completed)
+    }
+}
+
+// StreamSummary is delivered through an AsyncTask
+impl<E> NotResult for StreamSummary<E> {}
+
+type ErrorHandler<E> = Box<dyn FnMut(&E) -> ErrorAction + Send>;
+
+/// State shared by every clone of an [`Emitter`]
+struct Shared<E> {
+    emitted: AtomicUsize,
+    closed: AtomicBool,
+    stopped: AtomicBool,
+    errors: Mutex<Vec<E>>,
+    on_error: Mutex<Option<ErrorHandler<E>>>,
+}
+
+/// Producer handle that pushes items and routes errors as they happen
+///
+/// Clones share the same stream and summary, so a producer can fan work out
+/// to several tasks. The summary is finalized when the producer future
+/// returns; clones still running after that are not counted.
+pub struct Emitter<T, E = BoxError, S = AsyncStream<T>>
+where
+    T: NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+    sender: S::Sender,
+    shared: Arc<Shared<E>>,
+}
+
+impl<T, E, S> Clone for Emitter<T, E, S>
+where
+    T: NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T, E, S> Emitter<T, E, S>
+where
+    T: NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+    /// Push an item to the stream
+    ///
+    /// Returns false once the consumer has gone away or the error handler has
+    /// stopped the stream; the producer should wind down at that point.
+    pub fn emit(&self, item: T) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+        if self.sender.send_item(item).is_err() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("stream dropped by consumer, closing emitter");
+            self.shared.closed.store(true, Ordering::Release);
+            return false;
+        }
+        self.shared.emitted.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Report a recoverable error for the current item
+    ///
+    /// The error is passed to the handler set with [`StreamEmitter::on_error`]
+    /// and kept for the summary. Returns false if the stream should stop.
+    ///
+    /// The handler runs outside the emitter's locks, so it may report
+    /// through a clone of the emitter. Errors reported while it runs are
+    /// kept but not passed to it.
+    pub fn error(&self, error: impl Into<E>) -> bool {
+        let error = error.into();
+        let handler = self
+            .shared
+            .on_error
+            .lock()
+            .ok()
+            .and_then(|mut handler| handler.take());
+        let action = match handler {
+            Some(mut handler) => {
+                let action = handler(&error);
+                if let Ok(mut slot) = self.shared.on_error.lock() {
+                    *slot = Some(handler);
+                }
+                action
+            }
+            None => ErrorAction::Continue,
+        };
+        if let Ok(mut errors) = self.shared.errors.lock() {
+            errors.push(error);
+        }
+        if action == ErrorAction::Stop {
+            self.shared.stopped.store(true, Ordering::Release);
+        }
+        !self.is_closed()
+    }
+
+    /// Route a per-item result: items are emitted, errors are reported
+    pub fn emit_result<Err: Into<E>>(&self, result: Result<T, Err>) -> bool {
+        match result {
+            Ok(item) => self.emit(item),
+            Err(error) => self.error(error),
+        }
+    }
+
+    /// Returns true once no further items will be accepted
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::Acquire) || self.shared.stopped.load(Ordering::Acquire)
+    }
+}
+
+/// Builder for an incremental producer
+///
+/// ```ignore
+/// let (stream, summary) = StreamEmitter::<Line, ParseError>::new()
+///     .on_error(|_| ErrorAction::Continue)
+///     .spawn(|emitter| async move {
+///         for raw in input {
+///             emitter.emit_result(parse(raw));
+///         }
+///         Ok(())
+///     });
+/// ```
+pub struct StreamEmitter<T, E = BoxError, S = AsyncStream<T>> {
+    on_error: Option<ErrorHandler<E>>,
+    _marker: PhantomData<fn() -> (T, S)>,
+}
+
+impl<T, E> StreamEmitter<T, E>
+where
+    T: NotResult + Send + 'static,
+    E: Send + 'static,
+{
+    /// Create a StreamEmitter for the default backend
+    pub fn new() -> Self {
+        Self::with_backend()
+    }
+}
+
+impl<T, E> Default for StreamEmitter<T, E>
+where
+    T: NotResult + Send + 'static,
+    E: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, E, S> StreamEmitter<T, E, S>
+where
+    T: NotResult + Send + 'static,
+    E: Send + 'static,
+    S: StreamBackend<T>,
+{
+    /// Create a StreamEmitter that emits into backend `S`
+    pub fn with_backend() -> Self {
+        Self {
+            on_error: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Decide per error whether the stream continues
+    ///
+    /// Without a handler every recoverable error is recorded and the stream
+    /// continues.
+    pub fn on_error<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&E) -> ErrorAction + Send + 'static,
+    {
+        self.on_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Start the producer, returning the stream and its eventual summary
+    ///
+    /// Returning `Err` from the producer is fatal: the stream ends and the
+    /// error is reported as [`StreamOutcome::Failed`].
+    #[track_caller]
+    pub fn spawn<F, Fut>(self, producer: F) -> (S, AsyncTask<StreamSummary<E>>)
+    where
+        F: FnOnce(Emitter<T, E, S>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        let (sender, stream) = S::channel();
+        let shared = Arc::new(Shared {
+            emitted: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            errors: Mutex::new(Vec::new()),
+            on_error: Mutex::new(self.on_error),
+        });
+        let emitter = Emitter {
+            sender,
+            shared: Arc::clone(&shared),
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        S::spawn(async move {
+            let result = producer(emitter).await;
+            let outcome = match result {
+                Err(error) => StreamOutcome::Failed(error),
+                Ok(()) if shared.stopped.load(Ordering::Acquire) => StreamOutcome::Stopped,
+                Ok(()) if shared.closed.load(Ordering::Acquire) => StreamOutcome::Closed,
+                Ok(()) => StreamOutcome::Completed,
+            };
+            let errors = match shared.errors.lock() {
+                Ok(mut errors) => std::mem::take(&mut *errors),
+                Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+            };
+            let _ = tx.send(StreamSummary {
+                emitted: shared.emitted.load(Ordering::Relaxed),
+                errors,
+                outcome,
+            });
+        });
+
+        (stream, AsyncTask::new(ZeroOneOrMany::one(rx)))
+    }
+}
+
+#[cfg(all(test, feature = "tokio-backend"))]
+mod tests {
+    use super::*;
+    use crate::StreamExt;
+
+    #[derive(Debug, PartialEq)]
+    struct BadLine(u32);
+
+    #[tokio::test]
+    async fn stream_continues_after_recoverable_errors() {
+        let (stream, summary) = StreamEmitter::<u32, BadLine>::new().spawn(|emitter| async move {
+            for n in 1..=5 {
+                if n % 2 == 0 {
+                    emitter.error(BadLine(n));
+                } else {
+                    emitter.emit(n);
+                }
+            }
+            Ok(())
+        });
+
+        assert_eq!(stream.collect().await, vec![1, 3, 5]);
+        let summary = summary.await;
+        assert_eq!(summary.emitted, 3);
+        assert_eq!(summary.errors, vec![BadLine(2), BadLine(4)]);
+        assert!(matches!(summary.outcome, StreamOutcome::Completed));
+    }
+
+    #[tokio::test]
+    async fn handler_can_stop_the_stream() {
+        let (stream, summary) = StreamEmitter::<u32>::new()
+            .on_error(|_| ErrorAction::Stop)
+            .spawn(|emitter| async move {
+                emitter.emit(1);
+                emitter.emit_result(Err::<u32, _>("boom"));
+                emitter.emit(2);
+                Ok(())
+            });
+
+        assert_eq!(stream.collect().await, vec![1]);
+        let summary = summary.await;
+        assert_eq!(summary.errors.len(), 1);
+        assert!(matches!(summary.outcome, StreamOutcome::Stopped));
+    }
+
+    #[tokio::test]
+    async fn handler_can_report_through_the_emitter() {
+        let slot: Arc<Mutex<Option<Emitter<u32, String>>>> = Arc::default();
+        let reporter = Arc::clone(&slot);
+        let (stream, summary) = StreamEmitter::<u32, String>::new()
+            .on_error(move |error| {
+                let emitter = reporter.lock().unwrap().clone();
+                if let Some(emitter) = emitter {
+                    emitter.error(format!("seen {error}"));
+                }
+                ErrorAction::Continue
+            })
+            .spawn(move |emitter| async move {
+                *slot.lock().unwrap() = Some(emitter.clone());
+                emitter.error("boom".to_string());
+                emitter.emit(1);
+                slot.lock().unwrap().take();
+                Ok(())
+            });
+
+        assert_eq!(stream.collect().await, vec![1]);
+        assert_eq!(summary.await.errors, ["seen boom", "boom"]);
+    }
+}
@@ -1,22 +1,21 @@
 //! EmitterBuilder - builds AsyncStream with error handling
 
-#[cfg(all(
-    feature = "crossbeam-backend",
-    not(feature = "tokio-backend"),
-    not(feature = "std-backend")
-))]
-use crate::stream::crossbeam::AsyncStream;
-#[cfg(all(feature = "std-backend", not(feature = "tokio-backend")))]
-use crate::stream::std::AsyncStream;
-#[cfg(feature = "tokio-backend")]
-use crate::stream::tokio::AsyncStream;
+use crate::AsyncStream;
+use crate::stream::{StreamBackend, StreamSender};
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
-use tokio::sync::mpsc;
 
 /// Builder that emits AsyncStream after handling Result
-pub struct EmitterBuilder<T> {
+///
+/// The whole batch is produced before anything is streamed; use
+/// [`StreamEmitter`](crate::StreamEmitter) to stream items as they are made.
+///
+/// The stream backend `S` defaults to [`AsyncStream`]; use
+/// [`EmitterBuilder::with_backend`] to emit into another backend.
+pub struct EmitterBuilder<T, S = AsyncStream<T>> {
     inner: Box<dyn EmitterImpl<T>>,
+    _backend: PhantomData<fn() -> S>,
 }
 
 /// Type alias for complex future return type
@@ -30,24 +29,41 @@ pub trait EmitterImpl<T>: Send {
 }
 
 impl<T: Send + 'static + super::NotResult> EmitterBuilder<T> {
-    /// Create a new EmitterBuilder
+    /// Create a new EmitterBuilder for the default backend
     pub fn new(inner: Box<dyn EmitterImpl<T>>) -> Self {
-        Self { inner }
+        Self::with_backend(inner)
+    }
+}
+
+impl<T, S> EmitterBuilder<T, S>
+where
+    T: Send + 'static + super::NotResult,
+    S: StreamBackend<T>,
+{
+    /// Create a new EmitterBuilder that emits into backend `S`
+    pub fn with_backend(inner: Box<dyn EmitterImpl<T>>) -> Self {
+        Self {
+            inner,
+            _backend: PhantomData,
+        }
     }
 
     /// Execute with error handling
-    pub fn emit<FOk, FErr>(self, on_ok: FOk, on_err: FErr) -> AsyncStream<T>
+    #[track_caller]
+    pub fn emit<FOk, FErr>(self, on_ok: FOk, on_err: FErr) -> S
     where
         FOk: FnOnce(Vec<T>) -> Vec<T> + Send + 'static,
         FErr: FnOnce(Box<dyn std::error::Error + Send>) + Send + 'static,
     {
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, stream) = S::channel();
 
-        tokio::spawn(async move {
+        S::spawn(async move {
             match self.inner.execute().await {
                 Ok(items) => {
                     for item in on_ok(items) {
-                        if tx.send(item).is_err() {
+                        if tx.send_item(item).is_err() {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!("stream dropped by consumer, stopping emitter");
                             break;
                         }
                     }
@@ -56,7 +72,7 @@ impl<T: Send + 'static + super::NotResult> EmitterBuilder<T> {
             }
         });
 
-        AsyncStream::new(rx)
+        stream
     }
 }
 
@@ -0,0 +1,321 @@
+//! Fan-out, broadcast and merge operations for TokioStream
+//!
+//! These operations let one stream feed several consumers, or several streams
+//! feed one consumer:
+//! - `fan_out` / `split_by`: lossless distribution to a fixed set of consumers
+//! - `broadcast`: lagging-aware publish/subscribe over a bounded ring buffer
+//! - `merge` / `select_all`: items from many streams in arrival order
+//! - `interleave`: items from many streams in strict round-robin order
+
+use crate::TokioStream;
+use futures::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use sugars_async_task::{NotResult, runtime};
+use sugars_collections::ZeroOneOrMany;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+
+impl<T> TokioStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    /// Distribute every item to `consumers` independent streams
+    ///
+    /// Each consumer receives its own clone of every item and never lags behind;
+    /// items are buffered per consumer until read. Dropping a consumer does not
+    /// affect the others. `consumers == 0` drains the source and yields `None`.
+    pub fn fan_out(self, consumers: usize) -> ZeroOneOrMany<TokioStream<T>>
+    where
+        T: Clone,
+    {
+        let (senders, streams): (Vec<_>, Vec<_>) = (0..consumers)
+            .map(|_| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (tx, TokioStream::new(rx))
+            })
+            .unzip();
+
+        runtime::spawn(async move {
+            use futures::StreamExt;
+            let mut source = self;
+            let mut senders = senders;
+            while let Some(item) = source.next().await {
+                senders.retain(|tx| tx.send(item.clone()).is_ok());
+                if senders.is_empty() && consumers > 0 {
+                    break;
+                }
+            }
+        });
+
+        ZeroOneOrMany::many(streams)
+    }
+
+    /// Route each item to one of two streams depending on `predicate`
+    ///
+    /// Returns `(matching, rest)`. Items are moved, not cloned, so no `Clone`
+    /// bound is needed.
+    pub fn split_by<F>(self, mut predicate: F) -> (TokioStream<T>, TokioStream<T>)
+    where
+        F: FnMut(&T) -> bool + Send + 'static,
+    {
+        let (matched_tx, matched_rx) = mpsc::unbounded_channel();
+        let (rest_tx, rest_rx) = mpsc::unbounded_channel();
+
+        runtime::spawn(async move {
+            use futures::StreamExt;
+            let mut source = self;
+            let mut matched_open = true;
+            let mut rest_open = true;
+            while let Some(item) = source.next().await {
+                if predicate(&item) {
+                    matched_open = matched_open && matched_tx.send(item).is_ok();
+                } else {
+                    rest_open = rest_open && rest_tx.send(item).is_ok();
+                }
+                if !matched_open && !rest_open {
+                    break;
+                }
+            }
+        });
+
+        (TokioStream::new(matched_rx), TokioStream::new(rest_rx))
+    }
+
+    /// Prepare a lagging-aware broadcast of this stream
+    ///
+    /// `capacity` bounds how many items a slow subscriber may fall behind by.
+    /// Once exceeded, the oldest items are skipped for that subscriber and
+    /// counted in [`BroadcastStats::lagged`]. Subscribe first, then call
+    /// [`Broadcast::connect`] to start pumping items.
+    pub fn broadcast(self, capacity: usize) -> Broadcast<T>
+    where
+        T: Clone,
+    {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Broadcast {
+            source: self,
+            sender,
+            stats: BroadcastStats::default(),
+        }
+    }
+
+    /// Merge streams into one, yielding items in arrival order
+    pub fn merge(streams: ZeroOneOrMany<TokioStream<T>>) -> TokioStream<T> {
+        Self::select_all(Vec::from(streams))
+    }
+
+    /// Poll all streams and yield whichever item is ready first
+    ///
+    /// The merged stream ends once every input stream has ended.
+    pub fn select_all<I>(streams: I) -> TokioStream<T>
+    where
+        I: IntoIterator<Item = TokioStream<T>>,
+    {
+        TokioStream::from_adapter(futures::stream::select_all(streams))
+    }
+
+    /// Take one item from each stream in turn
+    ///
+    /// Strictly fair: the next stream is not polled until the current one has
+    /// produced an item or ended. Ended streams are dropped from the rotation.
+    pub fn interleave(streams: ZeroOneOrMany<TokioStream<T>>) -> TokioStream<T> {
+        TokioStream::from_adapter(Interleave {
+            streams: Vec::from(streams),
+            next: 0,
+        })
+    }
+}
+
+/// A stream that has been prepared for broadcasting but not yet started
+pub struct Broadcast<T>
+where
+    T: NotResult,
+{
+    source: TokioStream<T>,
+    sender: broadcast::Sender<T>,
+    stats: BroadcastStats,
+}
+
+impl<T> Broadcast<T>
+where
+    T: NotResult + Clone + Send + 'static,
+{
+    /// Create a new subscriber that sees every item pumped after `connect`
+    pub fn subscribe(&self) -> TokioStream<T> {
+        let lagged = Arc::clone(&self.stats.lagged);
+        let receiver = BroadcastStream::new(self.sender.subscribe());
+
+        TokioStream::from_adapter(futures::StreamExt::filter_map(receiver, move |item| {
+            let item = match item {
+                Ok(item) => Some(item),
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    lagged.fetch_add(skipped, Ordering::Relaxed);
+                    None
+                }
+            };
+            std::future::ready(item)
+        }))
+    }
+
+    /// Number of subscribers created so far that are still alive
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Start pumping the source into all subscribers
+    ///
+    /// Subscribers end once the source ends. Pumping stops early once every
+    /// subscriber has been dropped.
+    pub fn connect(self) -> BroadcastStats {
+        let Broadcast {
+            source,
+            sender,
+            stats,
+        } = self;
+        let forwarded = Arc::clone(&stats.forwarded);
+
+        runtime::spawn(async move {
+            use futures::StreamExt;
+            let mut source = source;
+            while let Some(item) = source.next().await {
+                if sender.send(item).is_err() {
+                    break;
+                }
+                forwarded.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        stats
+    }
+}
+
+/// Live counters for a connected broadcast
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastStats {
+    forwarded: Arc<AtomicU64>,
+    lagged: Arc<AtomicU64>,
+}
+
+impl BroadcastStats {
+    /// Items pumped from the source into the broadcast channel
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    /// Items skipped across all subscribers because they fell behind
+    pub fn lagged(&self) -> u64 {
+        self.lagged.load(Ordering::Relaxed)
+    }
+}
+
+/// Round-robin adapter behind `TokioStream::interleave`
+struct Interleave<T>
+where
+    T: NotResult,
+{
+    streams: Vec<TokioStream<T>>,
+    next: usize,
+}
+
+impl<T> Stream for Interleave<T>
+where
+    T: NotResult,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while !this.streams.is_empty() {
+            let index = this.next % this.streams.len();
+            match Pin::new(&mut this.streams[index]).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.next = index + 1;
+                    return Poll::Ready(Some(item));
+                }
+                Poll::Ready(None) => {
+                    this.streams.remove(index);
+                    this.next = index;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn stream_of(items: Vec<u32>) -> TokioStream<u32> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for item in items {
+            let _ = tx.send(item);
+        }
+        TokioStream::new(rx)
+    }
+
+    #[tokio::test]
+    async fn fan_out_delivers_every_item_to_every_consumer() {
+        let consumers = stream_of(vec![1, 2, 3]).fan_out(2);
+        assert_eq!(consumers.len(), 2);
+        for consumer in Vec::from(consumers) {
+            assert_eq!(consumer.collect::<Vec<_>>().await, vec![1, 2, 3]);
+        }
+    }
+
+    #[tokio::test]
+    async fn split_by_routes_items() {
+        let (even, odd) = stream_of(vec![1, 2, 3, 4]).split_by(|n| n % 2 == 0);
+        assert_eq!(even.collect::<Vec<_>>().await, vec![2, 4]);
+        assert_eq!(odd.collect::<Vec<_>>().await, vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_all_subscribers() {
+        let hub = stream_of(vec![1, 2, 3]).broadcast(8);
+        let first = hub.subscribe();
+        let second = hub.subscribe();
+        let stats = hub.connect();
+        assert_eq!(first.collect::<Vec<_>>().await, vec![1, 2, 3]);
+        assert_eq!(second.collect::<Vec<_>>().await, vec![1, 2, 3]);
+        assert_eq!(stats.forwarded(), 3);
+        assert_eq!(stats.lagged(), 0);
+    }
+
+    #[tokio::test]
+    async fn broadcast_counts_lagged_items() {
+        let hub = stream_of((0..10).collect()).broadcast(4);
+        let slow = hub.subscribe();
+        let stats = hub.connect();
+        while stats.forwarded() < 10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(slow.collect::<Vec<_>>().await, vec![6, 7, 8, 9]);
+        assert_eq!(stats.lagged(), 6);
+    }
+
+    #[tokio::test]
+    async fn interleave_is_round_robin() {
+        let streams = ZeroOneOrMany::many(vec![
+            stream_of(vec![1, 4, 6]),
+            stream_of(vec![2]),
+            stream_of(vec![3, 5]),
+        ]);
+        let items: Vec<_> = TokioStream::interleave(streams).collect().await;
+        assert_eq!(items, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn merge_yields_all_items() {
+        let streams = ZeroOneOrMany::many(vec![stream_of(vec![1, 2]), stream_of(vec![3])]);
+        let mut items: Vec<_> = TokioStream::merge(streams).collect().await;
+        items.sort();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+}
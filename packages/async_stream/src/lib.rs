@@ -1,30 +1,72 @@
 //! Asynchronous programming utilities
 //!
-//! Choose your runtime and get both AsyncTask and AsyncStream plus convenient macros:
-//! - `tokio-async`: Full Tokio ecosystem with mpsc channels
-//! - `std-async`: Runtime-agnostic using async-channel  
-//! - `crossbeam-async`: Compute-heavy workloads with crossbeam + async-channel
+//! Enable one or more stream backends; each is a distinct type and they can be
+//! mixed freely in one binary:
+//! - `tokio-backend`: [`TokioStream`] over Tokio mpsc channels
+//! - `std-backend`: [`ChannelStream`], runtime-agnostic over async-channel
+//! - `crossbeam-backend`: [`CrossbeamStream`] for compute-heavy producers over crossbeam
+//!
+//! [`AsyncStream`] aliases the preferred enabled backend, in the order above.
 
+pub mod actor;
+pub mod adapters;
+pub mod circuit;
+pub mod emitter;
 pub mod emitter_builder;
+#[cfg(feature = "tokio-backend")]
+pub mod fan_out;
+pub mod progress;
+#[cfg(feature = "record")]
+pub mod record;
 pub mod result_types;
 pub mod stream;
 pub mod stream_ext;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(not(any(
+    feature = "tokio-backend",
+    feature = "std-backend",
+    feature = "crossbeam-backend"
+)))]
+compile_error!(
+    "sugars_async_stream needs at least one of the `tokio-backend`, `std-backend` or `crossbeam-backend` features"
+);
+
+// Backend types, usable side by side
+#[cfg(feature = "std-backend")]
+pub use stream::ChannelStream;
+#[cfg(feature = "tokio-backend")]
+pub use stream::TokioStream;
+pub use stream::{BlockingIter, StreamBackend, StreamSender};
+#[cfg(feature = "crossbeam-backend")]
+pub use stream::{CrossbeamSender, CrossbeamStream};
 
-// Runtime-specific unified exports
+/// Default stream type, picked from the enabled backends
+#[cfg(feature = "tokio-backend")]
+pub type AsyncStream<T> = TokioStream<T>;
+/// Default stream type, picked from the enabled backends
+#[cfg(all(feature = "std-backend", not(feature = "tokio-backend")))]
+pub type AsyncStream<T> = ChannelStream<T>;
+/// Default stream type, picked from the enabled backends
 #[cfg(all(
     feature = "crossbeam-backend",
     not(feature = "tokio-backend"),
     not(feature = "std-backend")
 ))]
-pub use stream::crossbeam::AsyncStream;
-#[cfg(all(feature = "std-backend", not(feature = "tokio-backend")))]
-pub use stream::std::AsyncStream;
-#[cfg(feature = "tokio-backend")]
-pub use stream::tokio::AsyncStream;
+pub type AsyncStream<T> = CrossbeamStream<T>;
 
 // Core types available in all configurations
+pub use actor::{Actor, ActorBuilder, ActorError, ActorRef, Envelope, Reply, Supervision};
+pub use circuit::{CircuitBreaker, CircuitError, CircuitEvent, CircuitOpen, CircuitState};
+pub use emitter::{BoxError, Emitter, ErrorAction, StreamEmitter, StreamOutcome, StreamSummary};
 pub use emitter_builder::{EmitterBuilder, EmitterImpl};
-pub use result_types::{AsyncResult, AsyncResultChunk};
+#[cfg(feature = "tokio-backend")]
+pub use fan_out::{Broadcast, BroadcastStats};
+pub use progress::{Progress, ProgressEvent};
+#[cfg(feature = "record")]
+pub use record::RecordedItem;
+pub use result_types::{AsyncResult, AsyncResultChunk, IntoResult};
 pub use stream_ext::StreamExt;
 
 // Re-export from async_task
@@ -0,0 +1,248 @@
+//! Progress reporting for long-running AsyncTask and AsyncStream work
+//!
+//! A producer holds a [`Progress`] handle and updates it as work completes.
+//! Consumers observe the same handle either as an [`AsyncStream`] of
+//! [`ProgressEvent`]s or through a `tokio::sync::watch` receiver, which always
+//! holds the latest snapshot and is cheap to poll from a UI loop.
+
+use crate::AsyncStream;
+use crate::stream::StreamBackend;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Snapshot of a [`Progress`] handle
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressEvent {
+    /// Total units of work, if known
+    pub total: Option<u64>,
+    /// Units of work completed so far
+    pub completed: u64,
+    /// Human readable description of the current step
+    pub message: Option<String>,
+    /// Completed units per second since the work started
+    pub rate: f64,
+    /// Time since the work started
+    pub elapsed: Duration,
+    /// Whether the producer has marked the work as finished
+    pub finished: bool,
+}
+
+impl ProgressEvent {
+    /// Completed fraction in `0.0..=1.0`, if the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| {
+            if total == 0 {
+                1.0
+            } else {
+                (self.completed as f64 / total as f64).min(1.0)
+            }
+        })
+    }
+
+    /// Estimated time remaining at the current rate, if the total is known
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total?.saturating_sub(self.completed);
+        if self.rate > 0.0 {
+            Some(Duration::from_secs_f64(remaining as f64 / self.rate))
+        } else {
+            None
+        }
+    }
+
+    fn initial(total: Option<u64>) -> Self {
+        Self {
+            total,
+            completed: 0,
+            message: None,
+            rate: 0.0,
+            elapsed: Duration::ZERO,
+            finished: false,
+        }
+    }
+}
+
+struct Inner {
+    sender: watch::Sender<ProgressEvent>,
+    started: Mutex<Instant>,
+}
+
+/// Shared, cloneable progress handle
+///
+/// Every clone updates and observes the same progress. Watchers are notified
+/// on each update; the event stream ends after the finished event or once
+/// every handle has been dropped.
+#[derive(Clone)]
+pub struct Progress {
+    inner: Arc<Inner>,
+}
+
+impl Progress {
+    /// Create a progress handle with an unknown total
+    pub fn new() -> Self {
+        Self::with_total_opt(None)
+    }
+
+    /// Create a progress handle for a known amount of work
+    pub fn with_total(total: u64) -> Self {
+        Self::with_total_opt(Some(total))
+    }
+
+    fn with_total_opt(total: Option<u64>) -> Self {
+        let (sender, _) = watch::channel(ProgressEvent::initial(total));
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                started: Mutex::new(Instant::now()),
+            }),
+        }
+    }
+
+    /// Start over with a new total, resetting the count, message and clock
+    pub fn reset(&self, total: Option<u64>) {
+        if let Ok(mut started) = self.inner.started.lock() {
+            *started = Instant::now();
+        }
+        self.inner
+            .sender
+            .send_replace(ProgressEvent::initial(total));
+    }
+
+    /// Set or change the total units of work
+    pub fn set_total(&self, total: u64) {
+        self.update(|event| event.total = Some(total));
+    }
+
+    /// Record `n` more completed units
+    pub fn inc(&self, n: u64) {
+        self.update(|event| event.completed = event.completed.saturating_add(n));
+    }
+
+    /// Set the completed count directly
+    pub fn set_completed(&self, completed: u64) {
+        self.update(|event| event.completed = completed);
+    }
+
+    /// Describe the current step
+    pub fn set_message(&self, message: impl Into<String>) {
+        let message = message.into();
+        self.update(|event| event.message = Some(message));
+    }
+
+    /// Record one completed unit along with a description of it
+    pub fn step(&self, message: impl Into<String>) {
+        let message = message.into();
+        self.update(|event| {
+            event.completed = event.completed.saturating_add(1);
+            event.message = Some(message);
+        });
+    }
+
+    /// Mark the work as finished
+    pub fn finish(&self) {
+        self.update(|event| event.finished = true);
+    }
+
+    /// Latest snapshot
+    pub fn snapshot(&self) -> ProgressEvent {
+        self.inner.sender.borrow().clone()
+    }
+
+    /// Watch receiver that always holds the latest snapshot
+    pub fn watch(&self) -> watch::Receiver<ProgressEvent> {
+        self.inner.sender.subscribe()
+    }
+
+    /// Stream of snapshots, starting with the current one
+    pub fn events(&self) -> AsyncStream<ProgressEvent> {
+        self.events_on()
+    }
+
+    /// Stream of snapshots on a specific backend
+    pub fn events_on<S: StreamBackend<ProgressEvent>>(&self) -> S {
+        let receiver = self.watch();
+        S::from_adapter(futures::stream::unfold(
+            (receiver, true, false),
+            |(mut receiver, first, done)| async move {
+                if done {
+                    return None;
+                }
+                if !first {
+                    receiver.changed().await.ok()?;
+                }
+                let event = receiver.borrow_and_update().clone();
+                let done = event.finished;
+                Some((event, (receiver, false, done)))
+            },
+        ))
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut ProgressEvent)) {
+        let elapsed = self
+            .inner
+            .started
+            .lock()
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        self.inner.sender.send_modify(|event| {
+            apply(event);
+            event.elapsed = elapsed;
+            let seconds = elapsed.as_secs_f64();
+            event.rate = if seconds > 0.0 {
+                event.completed as f64 / seconds
+            } else {
+                0.0
+            };
+        });
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Progress").field(&self.snapshot()).finish()
+    }
+}
+
+#[cfg(all(test, feature = "tokio-backend"))]
+mod tests {
+    use super::*;
+    use crate::StreamExt;
+
+    #[tokio::test]
+    async fn events_end_after_finish() {
+        let progress = Progress::with_total(2);
+        let events = progress.events();
+
+        let producer = progress.clone();
+        tokio::spawn(async move {
+            producer.step("first");
+            tokio::task::yield_now().await;
+            producer.step("second");
+            producer.finish();
+        });
+
+        let events = events.collect().await;
+        let last = events.last().expect("at least one event");
+        assert!(last.finished);
+        assert_eq!(last.completed, 2);
+        assert_eq!(last.fraction(), Some(1.0));
+        assert_eq!(last.message.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn watch_sees_latest_snapshot() {
+        let progress = Progress::new();
+        let watch = progress.watch();
+        progress.set_total(10);
+        progress.inc(4);
+        assert_eq!(watch.borrow().completed, 4);
+        assert_eq!(watch.borrow().fraction(), Some(0.4));
+    }
+}
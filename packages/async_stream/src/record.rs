@@ -0,0 +1,222 @@
+//! Record streams to disk and replay them later
+//!
+//! Enabled with the `record` feature. [`StreamExt::record_to`] writes every
+//! item that passes through a stream to a JSON Lines file, one
+//! [`RecordedItem`] per line, and [`StreamBackend::replay`] turns such a file
+//! back into a stream with the original (or scaled) timing:
+//!
+//! ```ignore
+//! // Capture a live conversation...
+//! let chunks = agent.chat("hello").record_to("fixtures/hello.jsonl")?;
+//!
+//! // ...and replay it offline, ten times faster
+//! let chunks = AsyncStream::<ChatMessageChunk>::replay("fixtures/hello.jsonl", 10.0)?;
+//! ```
+//!
+//! Replayed delays sleep on the Tokio clock, so replay needs a Tokio runtime
+//! and runs instantly under a paused clock.
+//!
+//! [`StreamExt::record_to`]: crate::StreamExt::record_to
+
+use crate::stream::StreamBackend;
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sugars_async_task::NotResult;
+use tokio::time::Instant;
+
+/// One line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedItem<T> {
+    /// Time since recording started, in microseconds
+    pub offset_us: u64,
+    /// Wall-clock time the item was seen, in milliseconds since the Unix epoch
+    pub unix_ms: u64,
+    /// The item itself
+    pub item: T,
+}
+
+/// Stream adapter for `StreamExt::record_to`
+///
+/// Items are forwarded unchanged. A failed write stops the recording but
+/// never the stream.
+pub struct Record<S> {
+    stream: S,
+    writer: Option<BufWriter<File>>,
+    started: Instant,
+}
+
+impl<S> Record<S> {
+    pub(crate) fn new(stream: S, path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            stream,
+            writer: Some(BufWriter::new(File::create(path)?)),
+            started: Instant::now(),
+        })
+    }
+
+    fn write<T: Serialize>(&mut self, item: &T) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        let line = RecordedItem {
+            offset_us: self.started.elapsed().as_micros() as u64,
+            unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_millis() as u64),
+            item,
+        };
+        // Flush per line so a crashing test still leaves a usable recording
+        let written = serde_json::to_writer(&mut *writer, &line)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush());
+        if let Err(_error) = written {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_error, "stream recording failed, no further items will be recorded");
+            self.writer = None;
+        }
+    }
+}
+
+impl<S: Unpin> Unpin for Record<S> {}
+
+impl<S> Stream for Record<S>
+where
+    S: Stream + Unpin,
+    S::Item: Serialize,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        let item = ready!(Pin::new(&mut this.stream).poll_next(cx));
+        match &item {
+            Some(item) => this.write(item),
+            None => this.writer = None,
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Read a recording and build a stream that re-emits it on backend `S`
+///
+/// The whole file is parsed up front so a corrupt recording fails here
+/// rather than halfway through a test. `speed` scales the recorded delays:
+/// `1.0` keeps the original timing, `2.0` plays twice as fast and
+/// `f64::INFINITY` emits everything without waiting.
+pub(crate) fn replay<T, S>(path: &Path, speed: f64) -> io::Result<S>
+where
+    T: DeserializeOwned + NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+    if speed.is_nan() || speed <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("replay speed must be positive, got {speed}"),
+        ));
+    }
+
+    let contents = std::fs::read_to_string(path)?;
+    let mut items = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: RecordedItem<T> = serde_json::from_str(line).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {error}", path.display(), index + 1),
+            )
+        })?;
+        items.push(recorded);
+    }
+
+    Ok(S::from_adapter(futures::stream::unfold(
+        (items.into_iter(), None::<Instant>),
+        move |(mut items, started)| async move {
+            let recorded = items.next()?;
+            let started = started.unwrap_or_else(Instant::now);
+            let due = Duration::from_micros(recorded.offset_us).div_f64(speed);
+            tokio::time::sleep_until(started + due).await;
+            Some((recorded.item, (items, Some(started))))
+        },
+    )))
+}
+
+#[cfg(all(test, feature = "tokio-backend"))]
+mod tests {
+    use super::*;
+    use crate::{AsyncStream, StreamExt};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Chunk {
+        content: String,
+    }
+
+    fn chunk(content: &str) -> Chunk {
+        Chunk {
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_reproduces_items_and_timing() {
+        let path = std::env::temp_dir()
+            .join(format!("sugars-record-{}", std::process::id()))
+            .join("chunks.jsonl");
+
+        let live = AsyncStream::from_adapter(futures::stream::unfold(0, |n| async move {
+            match n {
+                0 => Some((chunk("Hel"), 1)),
+                1 => {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    Some((chunk("lo"), 2))
+                }
+                _ => None,
+            }
+        }))
+        .record_to(&path)
+        .unwrap();
+        assert_eq!(live.collect().await, vec![chunk("Hel"), chunk("lo")]);
+
+        let started = Instant::now();
+        let replayed = AsyncStream::<Chunk>::replay(&path, 2.0).unwrap();
+        assert_eq!(replayed.collect().await, vec![chunk("Hel"), chunk("lo")]);
+        assert_eq!(started.elapsed(), Duration::from_millis(100));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn corrupt_lines_are_reported_with_their_position() {
+        let path =
+            std::env::temp_dir().join(format!("sugars-corrupt-{}.jsonl", std::process::id()));
+        std::fs::write(
+            &path,
+            "{\"offset_us\":0,\"unix_ms\":0,\"item\":1}\nnot json\n",
+        )
+        .unwrap();
+
+        let Err(error) = AsyncStream::<u32>::replay(&path, 1.0) else {
+            panic!("a corrupt recording should not replay");
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains(":2:"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -2,14 +2,25 @@
 //!
 //! These special Result types implement NotResult to bypass the negative impl restriction,
 //! allowing error handling within async operations while maintaining the "always unwrapped" pattern.
+//!
+//! Both types offer the familiar `Result` combinators (`into_ok`/`into_err` stand
+//! in for `Result::ok`/`Result::err`, whose names belong to the constructors),
+//! and [`IntoResult`] turns them back into a plain `Result` so `?` can be used
+//! on awaited values:
+//!
+//! ```ignore
+//! let config = load_config().await.into_result()?;
+//! ```
 
+use serde::{Deserialize, Serialize};
 use sugars_async_task::NotResult;
 
 /// A Result type that can be used with AsyncTask
 ///
 /// This type bypasses the negative impl restriction on Result types,
 /// allowing error handling within AsyncTask operations.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct AsyncResult<T, E> {
     inner: Result<T, E>,
 }
@@ -44,6 +55,61 @@ impl<T, E> AsyncResult<T, E> {
     pub fn is_err(&self) -> bool {
         self.inner.is_err()
     }
+
+    /// Converts into an Option holding the success value, discarding any error.
+    pub fn into_ok(self) -> Option<T> {
+        self.inner.ok()
+    }
+
+    /// Converts into an Option holding the error value, discarding any success.
+    pub fn into_err(self) -> Option<E> {
+        self.inner.err()
+    }
+
+    /// Maps the success value, leaving an error untouched.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> AsyncResult<U, E> {
+        AsyncResult {
+            inner: self.inner.map(f),
+        }
+    }
+
+    /// Maps the error value, leaving a success untouched.
+    pub fn map_err<F>(self, f: impl FnOnce(E) -> F) -> AsyncResult<T, F> {
+        AsyncResult {
+            inner: self.inner.map_err(f),
+        }
+    }
+
+    /// Chains a fallible operation on the success value.
+    ///
+    /// The closure may return a `Result` or another AsyncResult.
+    pub fn and_then<U, R>(self, f: impl FnOnce(T) -> R) -> AsyncResult<U, E>
+    where
+        R: Into<AsyncResult<U, E>>,
+    {
+        match self.inner {
+            Ok(value) => f(value).into(),
+            Err(error) => AsyncResult::err(error),
+        }
+    }
+
+    /// Recovers from an error with a fallible operation.
+    ///
+    /// The closure may return a `Result` or another AsyncResult.
+    pub fn or_else<F, R>(self, f: impl FnOnce(E) -> R) -> AsyncResult<T, F>
+    where
+        R: Into<AsyncResult<T, F>>,
+    {
+        match self.inner {
+            Ok(value) => AsyncResult::ok(value),
+            Err(error) => f(error).into(),
+        }
+    }
+
+    /// Returns the success value or computes one from the error.
+    pub fn unwrap_or_else(self, f: impl FnOnce(E) -> T) -> T {
+        self.inner.unwrap_or_else(f)
+    }
 }
 
 // Explicitly implement NotResult for AsyncResult
@@ -53,7 +119,8 @@ impl<T, E> NotResult for AsyncResult<T, E> {}
 ///
 /// This type is designed for streaming operations where each chunk
 /// might succeed or fail independently.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct AsyncResultChunk<T, E> {
     inner: Result<T, E>,
 }
@@ -88,6 +155,61 @@ impl<T, E> AsyncResultChunk<T, E> {
     pub fn is_err(&self) -> bool {
         self.inner.is_err()
     }
+
+    /// Converts into an Option holding the success value, discarding any error.
+    pub fn into_ok(self) -> Option<T> {
+        self.inner.ok()
+    }
+
+    /// Converts into an Option holding the error value, discarding any success.
+    pub fn into_err(self) -> Option<E> {
+        self.inner.err()
+    }
+
+    /// Maps the success value, leaving an error untouched.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> AsyncResultChunk<U, E> {
+        AsyncResultChunk {
+            inner: self.inner.map(f),
+        }
+    }
+
+    /// Maps the error value, leaving a success untouched.
+    pub fn map_err<F>(self, f: impl FnOnce(E) -> F) -> AsyncResultChunk<T, F> {
+        AsyncResultChunk {
+            inner: self.inner.map_err(f),
+        }
+    }
+
+    /// Chains a fallible operation on the success value.
+    ///
+    /// The closure may return a `Result` or another AsyncResultChunk.
+    pub fn and_then<U, R>(self, f: impl FnOnce(T) -> R) -> AsyncResultChunk<U, E>
+    where
+        R: Into<AsyncResultChunk<U, E>>,
+    {
+        match self.inner {
+            Ok(value) => f(value).into(),
+            Err(error) => AsyncResultChunk::err(error),
+        }
+    }
+
+    /// Recovers from an error with a fallible operation.
+    ///
+    /// The closure may return a `Result` or another AsyncResultChunk.
+    pub fn or_else<F, R>(self, f: impl FnOnce(E) -> R) -> AsyncResultChunk<T, F>
+    where
+        R: Into<AsyncResultChunk<T, F>>,
+    {
+        match self.inner {
+            Ok(value) => AsyncResultChunk::ok(value),
+            Err(error) => f(error).into(),
+        }
+    }
+
+    /// Returns the success value or computes one from the error.
+    pub fn unwrap_or_else(self, f: impl FnOnce(E) -> T) -> T {
+        self.inner.unwrap_or_else(f)
+    }
 }
 
 // Explicitly implement NotResult for AsyncResultChunk
@@ -105,3 +227,79 @@ impl<T, E> From<Result<T, E>> for AsyncResultChunk<T, E> {
         Self { inner: result }
     }
 }
+
+impl<T, E> From<AsyncResult<T, E>> for Result<T, E> {
+    fn from(result: AsyncResult<T, E>) -> Self {
+        result.inner
+    }
+}
+
+impl<T, E> From<AsyncResultChunk<T, E>> for Result<T, E> {
+    fn from(chunk: AsyncResultChunk<T, E>) -> Self {
+        chunk.inner
+    }
+}
+
+/// Conversion into a plain `Result`, so `?` works on async result values
+pub trait IntoResult<T, E> {
+    /// Converts into the equivalent `Result`.
+    fn into_result(self) -> Result<T, E>;
+}
+
+impl<T, E> IntoResult<T, E> for Result<T, E> {
+    fn into_result(self) -> Result<T, E> {
+        self
+    }
+}
+
+impl<T, E> IntoResult<T, E> for AsyncResult<T, E> {
+    fn into_result(self) -> Result<T, E> {
+        self.inner
+    }
+}
+
+impl<T, E> IntoResult<T, E> for AsyncResultChunk<T, E> {
+    fn into_result(self) -> Result<T, E> {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> AsyncResult<u32, String> {
+        input.parse::<u32>().map_err(|e| e.to_string()).into()
+    }
+
+    fn double_parsed(input: &str) -> Result<u32, String> {
+        let value = parse(input).into_result()?;
+        Ok(value * 2)
+    }
+
+    #[test]
+    fn combinators_follow_result_semantics() {
+        let chained = parse("4").map(|n| n + 1).and_then(|n| {
+            if n > 3 {
+                Ok(n)
+            } else {
+                Err("small".to_string())
+            }
+        });
+        assert_eq!(chained, AsyncResult::ok(5));
+
+        let recovered = parse("x").or_else(|_| AsyncResult::<u32, String>::ok(0));
+        assert_eq!(recovered.into_ok(), Some(0));
+        assert_eq!(parse("x").unwrap_or_else(|e| e.len() as u32), 29);
+        assert_eq!(double_parsed("21"), Ok(42));
+    }
+
+    #[test]
+    fn serializes_like_result() {
+        let chunk: AsyncResultChunk<u32, String> = AsyncResultChunk::err("bad".into());
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert_eq!(json, r#"{"Err":"bad"}"#);
+        let back: AsyncResultChunk<u32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, chunk);
+    }
+}
@@ -0,0 +1,128 @@
+//! Standard library async stream implementation using async-channel
+
+use super::{BlockingIter, StreamBackend, StreamSender};
+use futures::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use sugars_async_task::{AsyncTask, NotResult};
+
+/// Generic async stream wrapper for streaming operations with async-channel
+///
+/// IMPORTANT: ChannelStream must never contain Result types - all error handling
+/// should be done internally before sending items to the stream.
+///
+/// Producers spawned through this backend run on a dedicated thread driven by
+/// `futures::executor::block_on`, so no async runtime is required.
+pub struct ChannelStream<T>
+where
+    T: NotResult, // T cannot be any Result type
+{
+    source: Pin<Box<dyn Stream<Item = T> + Send>>,
+}
+
+impl<T> ChannelStream<T>
+where
+    T: NotResult + Send + 'static, // T cannot be any Result type
+{
+    /// Create a new ChannelStream from an async-channel receiver
+    pub fn new(receiver: async_channel::Receiver<T>) -> Self {
+        Self {
+            source: Box::pin(receiver),
+        }
+    }
+
+    /// Create a ChannelStream that yields the given items and then closes
+    pub fn from_vec(items: Vec<T>) -> Self
+    where
+        T: Send + 'static,
+    {
+        Self::from_adapter(futures::stream::iter(items))
+    }
+
+    /// Wrap a poll-based stream without spawning a forwarding task
+    pub fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        Self {
+            source: Box::pin(adapter),
+        }
+    }
+
+    /// Create a ChannelStream from a futures Stream
+    pub fn from_stream<S>(stream: S) -> AsyncTask<Vec<T>>
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        Self::from_adapter(stream).collect_async()
+    }
+
+    /// Collect all items from the stream into a Vec
+    pub fn collect_async(self) -> AsyncTask<Vec<T>> {
+        StreamBackend::collect_async(self)
+    }
+
+    /// Consume the stream as a blocking Iterator for synchronous code
+    pub fn blocking_iter(self) -> BlockingIter<Self> {
+        StreamBackend::blocking_iter(self)
+    }
+}
+
+impl<T> FromIterator<T> for ChannelStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T> Stream for ChannelStream<T>
+where
+    T: NotResult,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.source.as_mut().poll_next(cx)
+    }
+}
+
+impl<T: Send + 'static> StreamSender<T> for async_channel::Sender<T> {
+    fn send_item(&self, item: T) -> Result<(), T> {
+        self.try_send(item).map_err(|e| e.into_inner())
+    }
+}
+
+impl<T> StreamBackend<T> for ChannelStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    type Sender = async_channel::Sender<T>;
+    type Rebind<U>
+        = ChannelStream<U>
+    where
+        U: NotResult + Send + 'static;
+
+    fn channel() -> (Self::Sender, Self) {
+        let (tx, rx) = async_channel::unbounded();
+        (tx, Self::new(rx))
+    }
+
+    fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        ChannelStream::from_adapter(adapter)
+    }
+
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::in_current_span(future);
+        std::thread::spawn(move || futures::executor::block_on(future));
+    }
+}
@@ -1,95 +1,236 @@
-//! Crossbeam + async-channel hybrid implementation for compute-heavy workloads
+//! Crossbeam channel implementation for compute-heavy workloads
+//!
+//! Producers push through a plain `crossbeam::channel` from any thread without
+//! touching an async runtime. The sender wakes the consuming task after each
+//! send, so the stream can still be awaited like any other backend.
 
-use async_channel;
+use super::{BlockingIter, StreamBackend, StreamSender};
+use crossbeam::channel;
 use futures::Stream;
+use futures::task::AtomicWaker;
+use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use sugars_async_task::NotResult;
+use sugars_async_task::{AsyncTask, NotResult};
 
-/// Generic async stream wrapper for streaming operations with Crossbeam + async-channel
+/// Generic async stream wrapper for streaming operations with Crossbeam
 ///
-/// IMPORTANT: AsyncStream must never contain Result types - all error handling
+/// IMPORTANT: CrossbeamStream must never contain Result types - all error handling
 /// should be done internally before sending items to the stream.
-///
-/// This implementation uses async-channel for async compatibility with crossbeam
-/// for compute-heavy parallel processing.
-pub struct AsyncStream<T>
+pub struct CrossbeamStream<T>
 where
     T: NotResult, // T cannot be any Result type
 {
-    receiver: async_channel::Receiver<T>,
+    source: Source<T>,
 }
 
-impl<T> AsyncStream<T>
-where
-    T: NotResult, // T cannot be any Result type
-{
-    /// Create a new AsyncStream from a crossbeam receiver
-    pub fn new(receiver: channel::Receiver<T>) -> Self {
+/// Where a CrossbeamStream pulls its items from
+enum Source<T> {
+    /// Items pushed through a crossbeam channel by a [`CrossbeamSender`]
+    Channel {
+        receiver: channel::Receiver<T>,
+        waker: Arc<AtomicWaker>,
+    },
+    /// Items polled in place from a wrapped stream adapter
+    Adapter(Pin<Box<dyn Stream<Item = T> + Send>>),
+}
+
+/// Sending half of a [`CrossbeamStream`]
+///
+/// Wraps a crossbeam sender and wakes the consuming task on every send and
+/// when the last sender is dropped.
+pub struct CrossbeamSender<T> {
+    sender: Option<channel::Sender<T>>,
+    waker: Arc<AtomicWaker>,
+}
+
+impl<T> CrossbeamSender<T> {
+    /// Send an item to the stream, waking the consumer
+    pub fn send(&self, item: T) -> Result<(), channel::SendError<T>> {
+        let result = match &self.sender {
+            Some(sender) => sender.send(item),
+            None => Err(channel::SendError(item)),
+        };
+        self.waker.wake();
+        result
+    }
+}
+
+impl<T> Clone for CrossbeamSender<T> {
+    fn clone(&self) -> Self {
         Self {
-            receiver,
-            waker: Arc::new(Mutex::new(None)),
+            sender: self.sender.clone(),
+            waker: Arc::clone(&self.waker),
         }
     }
+}
+
+impl<T> Drop for CrossbeamSender<T> {
+    fn drop(&mut self) {
+        // Disconnect before waking so the consumer observes the closed channel
+        drop(self.sender.take());
+        self.waker.wake();
+    }
+}
+
+impl<T: Send + 'static> StreamSender<T> for CrossbeamSender<T> {
+    fn send_item(&self, item: T) -> Result<(), T> {
+        self.send(item).map_err(|e| e.into_inner())
+    }
+}
+
+impl<T> CrossbeamStream<T>
+where
+    T: NotResult + Send + 'static, // T cannot be any Result type
+{
+    /// Create an unbounded crossbeam channel feeding a new CrossbeamStream
+    pub fn channel() -> (CrossbeamSender<T>, Self) {
+        let (sender, receiver) = channel::unbounded();
+        let waker = Arc::new(AtomicWaker::new());
+        (
+            CrossbeamSender {
+                sender: Some(sender),
+                waker: Arc::clone(&waker),
+            },
+            Self {
+                source: Source::Channel { receiver, waker },
+            },
+        )
+    }
 
-    /// Create an AsyncStream from a futures Stream
-    pub fn from_stream<S>(stream: S) -> super::AsyncTask<Vec<T>>
+    /// Create a CrossbeamStream that yields the given items and then closes
+    pub fn from_vec(items: Vec<T>) -> Self
     where
-        S: Stream<Item = T> + Send + 'static,
         T: Send + 'static,
     {
-        let (tx, rx) = channel::unbounded();
-
-        std::thread::spawn(move || {
-            use futures::StreamExt;
-            let rt = tokio::runtime::Handle::current();
-            rt.block_on(async move {
-                let mut stream = std::pin::pin!(stream);
-                while let Some(item) = stream.next().await {
-                    if tx.send(item).is_err() {
-                        break;
-                    }
-                }
-            });
-        });
+        Self::from_adapter(futures::stream::iter(items))
+    }
 
-        AsyncStream::new(rx).collect_async()
+    /// Wrap a poll-based stream without spawning a forwarding task
+    pub fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        Self {
+            source: Source::Adapter(Box::pin(adapter)),
+        }
     }
 
-    /// Collect all items from the stream into a Vec
-    pub fn collect_async(self) -> super::AsyncTask<Vec<T>>
+    /// Create a CrossbeamStream from a futures Stream
+    pub fn from_stream<S>(stream: S) -> AsyncTask<Vec<T>>
     where
-        T: Send + 'static,
+        S: Stream<Item = T> + Send + 'static,
     {
-        super::AsyncTask::new(async move {
-            let mut items = Vec::new();
-            let receiver = self.receiver;
+        Self::from_adapter(stream).collect_async()
+    }
 
-            while let Ok(item) = receiver.recv() {
-                items.push(item);
-            }
+    /// Collect all items from the stream into a Vec
+    pub fn collect_async(self) -> AsyncTask<Vec<T>> {
+        StreamBackend::collect_async(self)
+    }
+
+    /// Consume the stream as a blocking Iterator for synchronous code
+    ///
+    /// Channel-backed streams block on the crossbeam receiver directly and
+    /// never touch an async runtime.
+    pub fn blocking_iter(self) -> BlockingIter<Self> {
+        StreamBackend::blocking_iter(self)
+    }
+}
 
-            items
-        })
+// Items are only ever moved out of the channel, never pinned in place
+impl<T: NotResult> Unpin for CrossbeamStream<T> {}
+
+impl<T> FromIterator<T> for CrossbeamStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
     }
 }
 
-impl<T> Stream for AsyncStream<T>
+impl<T> Stream for CrossbeamStream<T>
 where
     T: NotResult,
 {
     type Item = T;
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Store the waker for crossbeam channel notifications
-        if let Ok(mut waker_guard) = self.waker.lock() {
-            *waker_guard = Some(cx.waker().clone());
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (receiver, waker) = match &mut self.source {
+            Source::Channel { receiver, waker } => (receiver, waker),
+            Source::Adapter(adapter) => return adapter.as_mut().poll_next(cx),
+        };
+
+        match receiver.try_recv() {
+            Ok(item) => return Poll::Ready(Some(item)),
+            Err(channel::TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(channel::TryRecvError::Empty) => {}
         }
 
-        match self.receiver.try_recv() {
+        // Register before re-checking so a send racing with this poll is not lost
+        waker.register(cx.waker());
+        match receiver.try_recv() {
             Ok(item) => Poll::Ready(Some(item)),
-            Err(channel::TryRecvError::Empty) => Poll::Pending,
             Err(channel::TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(channel::TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl<T> StreamBackend<T> for CrossbeamStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    type Sender = CrossbeamSender<T>;
+    type Rebind<U>
+        = CrossbeamStream<U>
+    where
+        U: NotResult + Send + 'static;
+
+    fn channel() -> (Self::Sender, Self) {
+        CrossbeamStream::channel()
+    }
+
+    fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        CrossbeamStream::from_adapter(adapter)
+    }
+
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::in_current_span(future);
+        std::thread::spawn(move || futures::executor::block_on(future));
+    }
+
+    fn blocking_next(&mut self) -> Option<T> {
+        match &mut self.source {
+            Source::Channel { receiver, .. } => receiver.recv().ok(),
+            Source::Adapter(adapter) => {
+                futures::executor::block_on(futures::StreamExt::next(adapter))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_iter_reads_channel_directly() {
+        let (tx, stream) = CrossbeamStream::channel();
+        std::thread::spawn(move || {
+            for n in 1..=3u32 {
+                let _ = tx.send(n);
+            }
+        });
+        assert_eq!(stream.blocking_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
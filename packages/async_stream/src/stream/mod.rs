@@ -1,29 +1,157 @@
 //! Async stream with built-in error handling and collection support
 //!
-//! This module provides feature-gated implementations for different async runtimes:
-//! - tokio-async: Uses tokio::sync::mpsc for Tokio ecosystem
-//! - std-async: Uses async-channel (runtime-agnostic)  
-//! - crossbeam-async: Uses async-channel + crossbeam for compute-heavy workloads
+//! Each backend is a distinct type and can be enabled alongside the others:
+//! - `tokio-backend`: [`TokioStream`] over tokio::sync::mpsc for the Tokio ecosystem
+//! - `std-backend`: [`ChannelStream`] over async-channel (runtime-agnostic)
+//! - `crossbeam-backend`: [`CrossbeamStream`] over crossbeam::channel for compute-heavy producers
+//!
+//! All backends implement [`StreamBackend`], which is what `StreamExt` and
+//! `EmitterBuilder` are written against.
+//!
+//! Synchronous callers can drain any backend with [`StreamBackend::blocking_iter`].
+
+use futures::Stream;
+use std::future::Future;
+use sugars_async_task::{AsyncTask, NotResult};
+use sugars_collections::ZeroOneOrMany;
 
 #[cfg(feature = "tokio-backend")]
 pub mod tokio;
 #[cfg(feature = "tokio-backend")]
-pub use tokio::AsyncStream;
+pub use tokio::TokioStream;
 
-#[cfg(all(feature = "std-backend", not(feature = "tokio-backend")))]
-pub mod std;
-#[cfg(all(feature = "std-backend", not(feature = "tokio-backend")))]
-pub use std::AsyncStream;
+#[cfg(feature = "std-backend")]
+pub mod channel;
+#[cfg(feature = "std-backend")]
+pub use channel::ChannelStream;
 
-#[cfg(all(
-    feature = "crossbeam-backend",
-    not(feature = "tokio-backend"),
-    not(feature = "std-backend")
-))]
+#[cfg(feature = "crossbeam-backend")]
 pub mod crossbeam;
+#[cfg(feature = "crossbeam-backend")]
+pub use crossbeam::{CrossbeamSender, CrossbeamStream};
+
+/// Producer half of a backend channel
+pub trait StreamSender<T>: Clone + Send + 'static {
+    /// Send an item, handing it back if the stream has been dropped
+    fn send_item(&self, item: T) -> Result<(), T>;
+}
+
+/// Shared interface implemented by every AsyncStream backend
+pub trait StreamBackend<T>: Stream<Item = T> + Send + Unpin + Sized + 'static
+where
+    T: NotResult + Send + 'static,
+{
+    /// Producer half returned by [`StreamBackend::channel`]
+    type Sender: StreamSender<T>;
+
+    /// The same backend carrying a different item type
+    type Rebind<U>: StreamBackend<U>
+    where
+        U: NotResult + Send + 'static;
+
+    /// Create an unbounded producer/stream pair
+    fn channel() -> (Self::Sender, Self);
+
+    /// Wrap a poll-based stream without spawning a forwarding task
+    fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static;
+
+    /// Run a producer future on this backend's executor
+    ///
+    /// With the `tracing` feature the producer inherits the caller's span.
+    #[track_caller]
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static;
+
+    /// Collect all items from the stream into a Vec
+    #[track_caller]
+    fn collect_async(self) -> AsyncTask<Vec<T>> {
+        let (tx, rx) = ::tokio::sync::oneshot::channel();
+        Self::spawn(async move {
+            let items = futures::StreamExt::collect::<Vec<T>>(self).await;
+            let _ = tx.send(items);
+        });
+        AsyncTask::new(ZeroOneOrMany::one(rx))
+    }
+
+    /// Block the current thread until the next item arrives
+    ///
+    /// The default polls on `futures::executor`; backends whose producers need
+    /// a specific runtime override this.
+    fn blocking_next(&mut self) -> Option<T> {
+        futures::executor::block_on(futures::StreamExt::next(self))
+    }
+
+    /// Consume the stream as a blocking [`Iterator`] for synchronous code
+    fn blocking_iter(self) -> BlockingIter<Self> {
+        BlockingIter { stream: self }
+    }
+
+    /// Replay a recording made with [`StreamExt::record_to`](crate::StreamExt::record_to)
+    ///
+    /// `speed` scales the recorded timing: `1.0` is real time, `2.0` twice
+    /// as fast, `f64::INFINITY` without delays. Fails if the file cannot be
+    /// read or any line does not parse.
+    #[cfg(feature = "record")]
+    fn replay(path: impl AsRef<std::path::Path>, speed: f64) -> std::io::Result<Self>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        crate::record::replay(path.as_ref(), speed)
+    }
+}
+
+/// Blocking iterator over a stream, created by [`StreamBackend::blocking_iter`]
+pub struct BlockingIter<S> {
+    stream: S,
+}
+
+impl<S, T> Iterator for BlockingIter<S>
+where
+    S: StreamBackend<T> + Stream<Item = T>,
+    T: NotResult + Send + 'static,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stream.blocking_next()
+    }
+}
+
 #[cfg(all(
-    feature = "crossbeam-backend",
-    not(feature = "tokio-backend"),
-    not(feature = "std-backend")
+    test,
+    feature = "tokio-backend",
+    feature = "std-backend",
+    feature = "crossbeam-backend"
 ))]
-pub use crossbeam::AsyncStream;
+mod tests {
+    use super::{ChannelStream, CrossbeamStream, StreamBackend, StreamSender, TokioStream};
+    use crate::StreamExt;
+
+    fn produce<S: StreamBackend<u32>>(items: Vec<u32>) -> S {
+        let (tx, stream) = S::channel();
+        S::spawn(async move {
+            for item in items {
+                let _ = tx.send_item(item);
+            }
+        });
+        stream
+    }
+
+    #[tokio::test]
+    async fn backends_coexist_in_one_binary() {
+        let tokio: TokioStream<u32> = produce(vec![1, 2]);
+        let channel: ChannelStream<u32> = produce(vec![3, 4]);
+        let crossbeam: CrossbeamStream<u32> = produce(vec![5, 6]);
+
+        let tokio = tokio.map_stream(|n| n * 10).collect_async().await;
+        let channel = channel.map_stream(|n| n * 10).collect_async().await;
+        let crossbeam = crossbeam.map_stream(|n| n * 10).collect_async().await;
+
+        assert_eq!(tokio, vec![10, 20]);
+        assert_eq!(channel, vec![30, 40]);
+        assert_eq!(crossbeam, vec![50, 60]);
+    }
+}
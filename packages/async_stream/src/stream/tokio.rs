@@ -1,33 +1,66 @@
 //! Tokio-based async stream implementation
 
+use super::{BlockingIter, StreamBackend, StreamSender};
 use futures::Stream;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use sugars_async_task::{AsyncTask, NotResult};
+use sugars_async_task::{AsyncTask, NotResult, runtime};
 use sugars_collections::ZeroOneOrMany;
 use tokio::sync::mpsc;
 
 /// Generic async stream wrapper for streaming operations with Tokio
 ///
-/// IMPORTANT: AsyncStream must never contain Result types - all error handling
+/// IMPORTANT: TokioStream must never contain Result types - all error handling
 /// should be done internally before sending items to the stream.
-pub struct AsyncStream<T>
+pub struct TokioStream<T>
 where
     T: NotResult, // T cannot be any Result type
 {
-    receiver: mpsc::UnboundedReceiver<T>,
+    source: Source<T>,
 }
 
-impl<T> AsyncStream<T>
+/// Where a TokioStream pulls its items from
+enum Source<T> {
+    /// Items pushed by a producer task through an unbounded channel
+    Channel(mpsc::UnboundedReceiver<T>),
+    /// Items polled in place from a wrapped stream adapter
+    Adapter(Pin<Box<dyn Stream<Item = T> + Send>>),
+}
+
+impl<T> TokioStream<T>
 where
     T: NotResult, // T cannot be any Result type
 {
-    /// Create a new AsyncStream from an unbounded receiver
+    /// Create a new TokioStream from an unbounded receiver
     pub fn new(receiver: mpsc::UnboundedReceiver<T>) -> Self {
-        Self { receiver }
+        Self {
+            source: Source::Channel(receiver),
+        }
+    }
+
+    /// Create a TokioStream that yields the given items and then closes
+    pub fn from_vec(items: Vec<T>) -> Self
+    where
+        T: Send + 'static,
+    {
+        Self::from_adapter(futures::stream::iter(items))
     }
 
-    /// Create an AsyncStream from a futures Stream
+    /// Wrap a poll-based stream without spawning a forwarding task
+    ///
+    /// The adapter is polled directly by whoever consumes this TokioStream,
+    /// so backpressure and lag semantics of the adapter are preserved.
+    pub fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        Self {
+            source: Source::Adapter(Box::pin(adapter)),
+        }
+    }
+
+    /// Create a TokioStream from a futures Stream
     pub fn from_stream<S>(stream: S) -> AsyncTask<Vec<T>>
     where
         S: Stream<Item = T> + Send + 'static,
@@ -35,7 +68,7 @@ where
     {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             use futures::StreamExt;
             let mut stream = std::pin::pin!(stream);
             let mut items = Vec::new();
@@ -53,28 +86,98 @@ where
     where
         T: Send + 'static,
     {
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        let mut receiver = self.receiver;
+        StreamBackend::collect_async(self)
+    }
 
-        tokio::spawn(async move {
-            let mut items = Vec::new();
-            while let Some(item) = receiver.recv().await {
-                items.push(item);
-            }
-            let _ = tx.send(items);
-        });
+    /// Consume the stream as a blocking Iterator for synchronous code
+    ///
+    /// Works with or without a running runtime; panics if called from a
+    /// current-thread runtime, where waiting would deadlock.
+    pub fn blocking_iter(self) -> BlockingIter<Self>
+    where
+        T: Send + 'static,
+    {
+        StreamBackend::blocking_iter(self)
+    }
+}
 
-        AsyncTask::new(ZeroOneOrMany::one(rx))
+impl<T> FromIterator<T> for TokioStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
     }
 }
 
-impl<T> Stream for AsyncStream<T>
+impl<T> Stream for TokioStream<T>
 where
     T: NotResult,
 {
     type Item = T;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.receiver.poll_recv(cx)
+        match &mut self.source {
+            Source::Channel(receiver) => receiver.poll_recv(cx),
+            Source::Adapter(adapter) => adapter.as_mut().poll_next(cx),
+        }
+    }
+}
+
+impl<T: Send + 'static> StreamSender<T> for mpsc::UnboundedSender<T> {
+    fn send_item(&self, item: T) -> Result<(), T> {
+        self.send(item).map_err(|e| e.0)
+    }
+}
+
+impl<T> StreamBackend<T> for TokioStream<T>
+where
+    T: NotResult + Send + 'static,
+{
+    type Sender = mpsc::UnboundedSender<T>;
+    type Rebind<U>
+        = TokioStream<U>
+    where
+        U: NotResult + Send + 'static;
+
+    fn channel() -> (Self::Sender, Self) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, Self::new(rx))
+    }
+
+    fn from_adapter<S>(adapter: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        TokioStream::from_adapter(adapter)
+    }
+
+    #[track_caller]
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        runtime::spawn(future);
+    }
+
+    #[track_caller]
+    fn blocking_next(&mut self) -> Option<T> {
+        runtime::block_on(futures::StreamExt::next(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_iter_without_runtime() {
+        let (tx, stream) = <TokioStream<u32> as StreamBackend<u32>>::channel();
+        <TokioStream<u32> as StreamBackend<u32>>::spawn(async move {
+            for n in 1..=3 {
+                let _ = tx.send(n);
+            }
+        });
+        assert_eq!(stream.blocking_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
     }
 }
@@ -1,67 +1,105 @@
 //! Stream extension traits for async stream processing
 
-use crate::AsyncStream;
+#[cfg(feature = "tracing")]
+use crate::adapters::Traced;
+use crate::adapters::{
+    Filter, Map, OnChunk, OnResult, PartitionChunks, RateLimit, TapEach, TeeEach,
+};
+#[cfg(feature = "record")]
+use crate::record::Record;
+use crate::result_types::{AsyncResult, IntoResult};
+use crate::stream::StreamBackend;
 use core::future::Future;
 use std::vec::Vec;
-use sugars_async_task::{AsyncTask, NotResult};
+use sugars_async_task::{AsyncTask, NotResult, RateLimiter};
 use sugars_collections::ZeroOneOrMany;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
 //────────────────────────────────────────────────────────────────────────────
-// StreamExt – Fluent ops for AsyncStream<T>
+// StreamExt – Fluent ops for every AsyncStream backend
 //────────────────────────────────────────────────────────────────────────────
 
 /// Extension trait for streams that provides additional combinators for async stream operations.
-pub trait StreamExt<T>: Sized + 'static {
+///
+/// Implemented for every [`StreamBackend`]; combinators return a stream of the
+/// same backend as their input.
+pub trait StreamExt<T>: StreamBackend<T>
+where
+    T: NotResult + Send + 'static,
+{
     /// Processes each result in the stream with the provided function.
-    fn on_result<F>(self, f: F) -> AsyncStream<T>
+    fn on_result<F>(self, f: F) -> Self
     where
-        F: FnMut(Result<T, Error>) -> Result<T, Error> + Send + 'static,
-        T: NotResult;
+        F: FnMut(Result<T, Error>) -> Result<T, Error> + Send + 'static;
 
     /// Processes each chunk in the stream with the provided function.
-    fn on_chunk<F, U>(self, f: F) -> AsyncStream<U>
+    fn on_chunk<F, U>(self, f: F) -> Self::Rebind<U>
     where
         F: FnMut(Result<T, Error>) -> U + Send + 'static,
         U: Send + 'static + NotResult;
 
     /// Processes each error in the stream with the provided function.
-    fn on_error<F>(self, f: F) -> AsyncStream<T>
+    fn on_error<F>(self, f: F) -> Self
     where
-        F: FnMut(Error) + Send + 'static,
-        T: NotResult;
+        F: FnMut(Error) + Send + 'static;
 
     /// Applies a function to each item in the stream without consuming it.
-    fn tap_each(self, f: impl FnMut(&T) + Send + 'static) -> AsyncStream<T>
-    where
-        T: NotResult;
+    fn tap_each(self, f: impl FnMut(&T) + Send + 'static) -> Self;
 
-    /// Applies a function to each item while forwarding the original item.
-    fn tee_each(self, f: impl FnMut(T) + Send + 'static) -> AsyncStream<T>
+    /// Applies a function to a clone of each item while forwarding the original item.
+    fn tee_each(self, f: impl FnMut(T) + Send + 'static) -> Self
     where
-        T: NotResult;
+        T: Clone;
 
     /// Maps each item in the stream to a new type using the provided function.
     fn map_stream<U: Send + 'static + NotResult>(
         self,
         f: impl FnMut(T) -> U + Send + 'static,
-    ) -> AsyncStream<U>;
+    ) -> Self::Rebind<U>;
 
     /// Filters items in the stream based on a predicate function.
-    fn filter_stream(self, f: impl FnMut(&T) -> bool + Send + 'static) -> AsyncStream<T>
-    where
-        T: NotResult;
+    fn filter_stream(self, f: impl FnMut(&T) -> bool + Send + 'static) -> Self;
+
+    /// Names the stream for tracing.
+    ///
+    /// With the `tracing` feature the stream is polled inside an `async_stream`
+    /// span recording the name, caller location, item count and duration, with
+    /// an event when it closes or is dropped early. Otherwise a no-op.
+    #[track_caller]
+    fn named(self, name: &str) -> Self;
+
+    /// Records every item to a JSON Lines file as it passes through.
+    ///
+    /// Each line is a [`RecordedItem`](crate::record::RecordedItem) with the
+    /// item and when it was seen; replay it with
+    /// [`StreamBackend::replay`]. Fails only if the file cannot be created.
+    #[cfg(feature = "record")]
+    fn record_to(self, path: impl AsRef<std::path::Path>) -> std::io::Result<Self>
+    where
+        T: serde::Serialize;
+
+    /// Delays items so they are delivered no faster than `limiter` allows.
+    ///
+    /// The limiter is shared, so streams and tasks drawing on the same
+    /// limiter split one budget. Waiting works on every stream backend.
+    fn rate_limit(self, limiter: &RateLimiter) -> Self;
 
     /// Partitions the stream into chunks of the specified size.
-    fn partition_chunks(self, chunk_size: usize) -> AsyncStream<Vec<T>>
-    where
-        Vec<T>: NotResult;
+    fn partition_chunks(self, chunk_size: usize) -> Self::Rebind<Vec<T>>;
 
     /// Terminates the stream by collecting all values into a Vec.
-    fn collect(self) -> AsyncTask<Vec<T>>
+    fn collect(self) -> AsyncTask<Vec<T>>;
+
+    /// Collects the success values of a stream of result chunks.
+    ///
+    /// Stops at the first error chunk and resolves to that error; the rest of
+    /// the stream is dropped unread.
+    fn try_collect<U, E>(self) -> AsyncTask<AsyncResult<Vec<U>, E>>
     where
-        Vec<T>: NotResult;
+        T: IntoResult<U, E>,
+        U: Send + 'static,
+        E: Send + 'static;
 
     /// Terminates the stream, running an async function for each item.
     fn await_result<F, Fut>(self, f: F) -> AsyncTask<()>
@@ -76,55 +114,31 @@ pub trait StreamExt<T>: Sized + 'static {
         Fut: Future<Output = ()> + Send + 'static;
 }
 
-// Implementation of StreamExt for AsyncStream
-impl<T: Clone + Send + 'static + NotResult> StreamExt<T> for AsyncStream<T> {
-    fn on_result<F>(self, mut f: F) -> AsyncStream<T>
+// Implementation of StreamExt for every backend
+//
+// Every combinator wraps `self` in a poll-based adapter from `crate::adapters`;
+// nothing is spawned and no intermediate channel is allocated.
+impl<T, S> StreamExt<T> for S
+where
+    T: NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+    fn on_result<F>(self, f: F) -> Self
     where
         F: FnMut(Result<T, Error>) -> Result<T, Error> + Send + 'static,
     {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                match f(Ok(item)) {
-                    Ok(v) => {
-                        if tx.send(v).is_err() {
-                            break;
-                        }
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
-
-        AsyncStream::new(rx)
+        S::from_adapter(OnResult::new(self, f))
     }
 
-    fn on_chunk<F, U>(self, mut f: F) -> AsyncStream<U>
+    fn on_chunk<F, U>(self, f: F) -> Self::Rebind<U>
     where
-        Self: Send + 'static,
         F: FnMut(Result<T, Error>) -> U + Send + 'static,
         U: Send + 'static + NotResult,
     {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                let result = f(Ok(item));
-                if tx.send(result).is_err() {
-                    break;
-                }
-            }
-        });
-
-        AsyncStream::new(rx)
+        S::Rebind::<U>::from_adapter(OnChunk::new(self, f))
     }
 
-    fn on_error<F>(self, _f: F) -> AsyncStream<T>
+    fn on_error<F>(self, _f: F) -> Self
     where
         F: FnMut(Error) + Send + 'static,
     {
@@ -133,107 +147,81 @@ impl<T: Clone + Send + 'static + NotResult> StreamExt<T> for AsyncStream<T> {
         self
     }
 
-    fn tap_each(self, mut f: impl FnMut(&T) + Send + 'static) -> AsyncStream<T> {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                f(&item);
-                if tx.send(item).is_err() {
-                    break;
-                }
-            }
-        });
-
-        AsyncStream::new(rx)
+    fn tap_each(self, f: impl FnMut(&T) + Send + 'static) -> Self {
+        S::from_adapter(TapEach::new(self, f))
     }
 
-    fn tee_each(self, mut f: impl FnMut(T) + Send + 'static) -> AsyncStream<T> {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                f(item.clone());
-                if tx.send(item).is_err() {
-                    break;
-                }
-            }
-        });
-
-        AsyncStream::new(rx)
+    fn tee_each(self, f: impl FnMut(T) + Send + 'static) -> Self
+    where
+        T: Clone,
+    {
+        S::from_adapter(TeeEach::new(self, f))
     }
 
     fn map_stream<U: Send + 'static + NotResult>(
         self,
-        mut f: impl FnMut(T) -> U + Send + 'static,
-    ) -> AsyncStream<U> {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        f: impl FnMut(T) -> U + Send + 'static,
+    ) -> Self::Rebind<U> {
+        S::Rebind::<U>::from_adapter(Map::new(self, f))
+    }
 
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                if tx.send(f(item)).is_err() {
-                    break;
-                }
-            }
-        });
+    fn filter_stream(self, f: impl FnMut(&T) -> bool + Send + 'static) -> Self {
+        S::from_adapter(Filter::new(self, f))
+    }
 
-        AsyncStream::new(rx)
+    #[cfg(feature = "tracing")]
+    #[track_caller]
+    fn named(self, name: &str) -> Self {
+        S::from_adapter(Traced::new(self, name, std::panic::Location::caller()))
     }
 
-    fn filter_stream(self, mut f: impl FnMut(&T) -> bool + Send + 'static) -> AsyncStream<T> {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    #[cfg(not(feature = "tracing"))]
+    fn named(self, _name: &str) -> Self {
+        self
+    }
 
-        tokio::spawn(async move {
-            use futures::StreamExt;
-            let mut stream = self;
-            while let Some(item) = stream.next().await {
-                if f(&item) && tx.send(item).is_err() {
-                    break;
-                }
-            }
-        });
+    #[cfg(feature = "record")]
+    fn record_to(self, path: impl AsRef<std::path::Path>) -> std::io::Result<Self>
+    where
+        T: serde::Serialize,
+    {
+        Ok(S::from_adapter(Record::new(self, path.as_ref())?))
+    }
+
+    fn rate_limit(self, limiter: &RateLimiter) -> Self {
+        S::from_adapter(RateLimit::new(self, limiter.clone()))
+    }
+
+    fn partition_chunks(self, chunk_size: usize) -> Self::Rebind<Vec<T>> {
+        S::Rebind::<Vec<T>>::from_adapter(PartitionChunks::new(self, chunk_size))
+    }
 
-        AsyncStream::new(rx)
+    fn collect(self) -> AsyncTask<Vec<T>> {
+        StreamBackend::collect_async(self)
     }
 
-    fn partition_chunks(self, chunk_size: usize) -> AsyncStream<Vec<T>>
+    fn try_collect<U, E>(self) -> AsyncTask<AsyncResult<Vec<U>, E>>
     where
-        Vec<T>: NotResult,
+        T: IntoResult<U, E>,
+        U: Send + 'static,
+        E: Send + 'static,
     {
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-        tokio::spawn(async move {
-            use futures::StreamExt;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        S::spawn(async move {
             let mut stream = self;
-            let mut buffer = Vec::with_capacity(chunk_size);
-
-            while let Some(item) = stream.next().await {
-                buffer.push(item);
-                if buffer.len() >= chunk_size {
-                    let chunk = std::mem::replace(&mut buffer, Vec::with_capacity(chunk_size));
-                    if tx.send(chunk).is_err() {
-                        break;
+            let mut items = Vec::new();
+            while let Some(chunk) = futures::StreamExt::next(&mut stream).await {
+                match chunk.into_result() {
+                    Ok(item) => items.push(item),
+                    Err(error) => {
+                        let _ = tx.send(AsyncResult::err(error));
+                        return;
                     }
                 }
             }
-
-            // Send remaining items
-            if !buffer.is_empty() {
-                let _ = tx.send(buffer);
-            }
+            let _ = tx.send(AsyncResult::ok(items));
         });
-
-        AsyncStream::new(rx)
-    }
-
-    fn collect(self) -> AsyncTask<Vec<T>> {
-        self.collect_async()
+        AsyncTask::new(ZeroOneOrMany::one(rx))
     }
 
     fn await_result<F, Fut>(self, mut _f: F) -> AsyncTask<()>
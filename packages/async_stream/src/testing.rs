@@ -0,0 +1,281 @@
+//! Deterministic test helpers for code built on AsyncStream
+//!
+//! Enabled with the `testing` feature. Everything here is designed to run on
+//! a paused Tokio clock, where sleeps complete instantly and in order:
+//!
+//! ```ignore
+//! use sugars_async_stream::testing::{Script, run_paused};
+//! use sugars_async_stream::assert_stream_eq;
+//!
+//! run_paused(async {
+//!     let stream = Script::new()
+//!         .emit("Hel")
+//!         .delay(Duration::from_secs(5))
+//!         .emit("lo")
+//!         .into_stream();
+//!     assert_stream_eq!(stream, ["Hel", "lo"]);
+//! });
+//! ```
+//!
+//! `#[tokio::test(start_paused = true)]` gives the same clock in async tests.
+
+use crate::{AsyncStream, NotResult};
+use futures::Stream;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write as _};
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// One step of a [`Script`]
+enum Step<T> {
+    Emit(T),
+    EmitWith(Box<dyn FnOnce() -> T + Send>),
+    Delay(Duration),
+    Run(Box<dyn FnOnce() + Send>),
+}
+
+/// Scripted producer for feeding code under test
+///
+/// Steps run lazily as the stream is polled, so delays advance the (paused)
+/// clock exactly where they appear in the script.
+pub struct Script<T> {
+    steps: VecDeque<Step<T>>,
+}
+
+impl<T> Script<T>
+where
+    T: NotResult + Send + 'static,
+{
+    /// Start an empty script
+    pub fn new() -> Self {
+        Self {
+            steps: VecDeque::new(),
+        }
+    }
+
+    /// Emit an item
+    pub fn emit(mut self, item: T) -> Self {
+        self.steps.push_back(Step::Emit(item));
+        self
+    }
+
+    /// Emit each item in order
+    pub fn emit_all(mut self, items: impl IntoIterator<Item = T>) -> Self {
+        self.steps.extend(items.into_iter().map(Step::Emit));
+        self
+    }
+
+    /// Emit an item produced when this step is reached
+    pub fn emit_with(mut self, f: impl FnOnce() -> T + Send + 'static) -> Self {
+        self.steps.push_back(Step::EmitWith(Box::new(f)));
+        self
+    }
+
+    /// Sleep on the Tokio clock before the next step
+    pub fn delay(mut self, duration: Duration) -> Self {
+        self.steps.push_back(Step::Delay(duration));
+        self
+    }
+
+    /// Run a side effect when this step is reached, e.g. flipping a flag the
+    /// code under test observes
+    pub fn run(mut self, f: impl FnOnce() + Send + 'static) -> Self {
+        self.steps.push_back(Step::Run(Box::new(f)));
+        self
+    }
+
+    /// Turn the script into a stream; it closes after the last step
+    pub fn into_stream(self) -> AsyncStream<T> {
+        AsyncStream::from_adapter(futures::stream::unfold(
+            self.steps,
+            |mut steps| async move {
+                loop {
+                    match steps.pop_front()? {
+                        Step::Emit(item) => return Some((item, steps)),
+                        Step::EmitWith(f) => return Some((f(), steps)),
+                        Step::Delay(duration) => tokio::time::sleep(duration).await,
+                        Step::Run(f) => f(),
+                    }
+                }
+            },
+        ))
+    }
+}
+
+impl<T> Default for Script<T>
+where
+    T: NotResult + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build a current-thread runtime whose clock starts paused
+pub fn paused_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("failed to build paused Tokio runtime")
+}
+
+/// Run a future to completion on a fresh paused-clock runtime
+pub fn run_paused<F: Future>(future: F) -> F::Output {
+    paused_runtime().block_on(future)
+}
+
+/// Collect every item of a stream; used by [`assert_stream_eq!`](crate::assert_stream_eq)
+pub async fn collect_all<S: Stream>(stream: S) -> Vec<S::Item> {
+    futures::StreamExt::collect(stream).await
+}
+
+/// Render each item of a stream on its own numbered line
+pub async fn snapshot<S>(stream: S) -> String
+where
+    S: Stream,
+    S::Item: Debug,
+{
+    let mut rendered = String::new();
+    for (index, item) in collect_all(stream).await.iter().enumerate() {
+        let _ = writeln!(rendered, "#{index} {item:?}");
+    }
+    rendered
+}
+
+/// Render the text of a chunk stream with `|` marking chunk boundaries
+///
+/// Suited to `ChatMessageChunk`/`CompletionChunk`-style streams, where both
+/// the final text and how it was split are worth pinning down:
+/// `snapshot_text(stream, |chunk| chunk.content.clone())` gives `"Hel|lo"`.
+pub async fn snapshot_text<S, F>(stream: S, mut text: F) -> String
+where
+    S: Stream,
+    F: FnMut(&S::Item) -> String,
+{
+    collect_all(stream)
+        .await
+        .iter()
+        .map(&mut text)
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Compare `actual` with the stored snapshot `name`
+///
+/// Snapshots live in `tests/snapshots/<name>.snap` under the crate being
+/// tested. Run with `UPDATE_SNAPSHOTS=1` to write missing snapshots and
+/// overwrite existing ones.
+///
+/// # Panics
+///
+/// Panics if the snapshot differs from `actual`, or is missing and
+/// `UPDATE_SNAPSHOTS` is not set.
+#[track_caller]
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join("tests")
+        .join("snapshots");
+    let path = dir.join(format!("{name}.snap"));
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+    match std::fs::read_to_string(&path) {
+        Ok(expected) if !update => {
+            assert_eq!(
+                actual,
+                expected,
+                "snapshot `{name}` does not match {} (rerun with UPDATE_SNAPSHOTS=1 to accept)",
+                path.display()
+            );
+        }
+        Err(_) if !update => panic!(
+            "snapshot missing, rerun with UPDATE_SNAPSHOTS=1 to write `{name}` to {}",
+            path.display()
+        ),
+        _ => {
+            std::fs::create_dir_all(&dir).expect("failed to create snapshot directory");
+            std::fs::write(&path, actual).expect("failed to write snapshot");
+        }
+    }
+}
+
+/// Assert that a stream yields exactly the expected items
+///
+/// Must be used inside an async context.
+///
+/// ```ignore
+/// assert_stream_eq!(stream, [1, 2, 3]);
+/// assert_stream_eq!(stream, expected_vec, "after {} retries", retries);
+/// ```
+#[macro_export]
+macro_rules! assert_stream_eq {
+    ($stream:expr, [$($item:expr),* $(,)?] $(,)?) => {
+        assert_eq!(
+            $crate::testing::collect_all($stream).await,
+            ::std::vec![$($item),*]
+        )
+    };
+    ($stream:expr, $expected:expr $(,)?) => {
+        assert_eq!($crate::testing::collect_all($stream).await, $expected)
+    };
+    ($stream:expr, $expected:expr, $($arg:tt)+) => {
+        assert_eq!($crate::testing::collect_all($stream).await, $expected, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::time::Instant;
+
+    #[test]
+    fn delays_run_on_the_paused_clock() {
+        run_paused(async {
+            let started = Instant::now();
+            let flag = Arc::new(AtomicBool::new(false));
+            let observed = Arc::clone(&flag);
+            let stream = Script::new()
+                .emit(1)
+                .delay(Duration::from_secs(60))
+                .run(move || flag.store(true, Ordering::SeqCst))
+                .emit_with(move || {
+                    if observed.load(Ordering::SeqCst) {
+                        2
+                    } else {
+                        0
+                    }
+                })
+                .into_stream();
+
+            assert_stream_eq!(stream, [1, 2]);
+            assert_eq!(started.elapsed(), Duration::from_secs(60));
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snapshots_render_chunks() {
+        let chunks = AsyncStream::from_vec(vec!["Hel".to_string(), "lo".to_string()]);
+        assert_eq!(snapshot_text(chunks, |chunk| chunk.clone()).await, "Hel|lo");
+
+        let chunks = AsyncStream::from_iter([1, 2]);
+        assert_eq!(snapshot(chunks).await, "#0 1\n#1 2\n");
+    }
+
+    #[test]
+    fn missing_snapshots_are_only_written_on_request() {
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            return;
+        }
+        let missing = std::panic::catch_unwind(|| assert_snapshot("never-recorded", "text"));
+        let message = missing.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("snapshot missing, rerun with UPDATE_SNAPSHOTS=1"));
+        let path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/never-recorded.snap");
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,275 @@
+//! Keyed cache of shared AsyncTask results
+//!
+//! [`TaskCache`] deduplicates concurrent loads: the first caller for a key
+//! starts the load, everyone else asking for that key while it runs (or after
+//! it finished, until the entry expires) gets a clone of the same
+//! [`SharedTask`].
+//!
+//! A load that panics expires as soon as it finishes, so the next request
+//! starts it again; see [`TaskCache::evict_when`] for loads
+//! whose value reports a failure.
+
+use crate::shared::SharedTask;
+use crate::task::{AsyncTask, NotResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A cached load and when it was started
+struct Entry<T>
+where
+    T: NotResult,
+{
+    task: SharedTask<T>,
+    started: Instant,
+    /// Set once the load panicked or failed
+    dead: Arc<AtomicBool>,
+}
+
+type Failed<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
+/// Deduplicating cache of in-flight and completed loads
+///
+/// Cloning a TaskCache yields another handle to the same cache.
+pub struct TaskCache<K, T>
+where
+    T: NotResult, // T cannot be any Result type
+{
+    entries: Arc<Mutex<HashMap<K, Entry<T>>>>,
+    ttl: Option<Duration>,
+    failed: Option<Failed<T>>,
+}
+
+impl<K, T> TaskCache<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: NotResult + Clone + Send + 'static,
+{
+    /// Create a cache whose entries never expire
+    pub fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl: None,
+            failed: None,
+        }
+    }
+
+    /// Create a cache whose completed entries expire `ttl` after their load started
+    ///
+    /// Loads still in flight are never expired, so a slow load is not
+    /// started twice.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl: Some(ttl),
+            failed: None,
+        }
+    }
+
+    /// Also expire a completed entry when `failed` returns true for its value
+    ///
+    /// For a `T` with its own error variant, so a failed load is retried by
+    /// the next request instead of being served until it expires.
+    pub fn evict_when(mut self, failed: impl Fn(&T) -> bool + Send + Sync + 'static) -> Self {
+        self.failed = Some(Arc::new(failed));
+        self
+    }
+
+    /// Get the shared load for `key`, starting it with `load` if needed
+    ///
+    /// `load` only runs when there is no live entry for the key.
+    pub fn get_or_load<F>(&self, key: K, load: F) -> SharedTask<T>
+    where
+        F: FnOnce() -> AsyncTask<T>,
+    {
+        let mut entries = self.lock();
+        if let Some(entry) = entries.get(&key)
+            && !self.is_expired(entry)
+        {
+            return entry.task.clone();
+        }
+
+        let dead = Arc::new(AtomicBool::new(false));
+        let task = self.evicting(load(), Arc::clone(&dead)).shared();
+        entries.insert(
+            key,
+            Entry {
+                task: task.clone(),
+                started: Instant::now(),
+                dead,
+            },
+        );
+        task
+    }
+
+    /// Wrap `task` so it marks its entry dead if it panics or fails
+    fn evicting(&self, task: AsyncTask<T>, dead: Arc<AtomicBool>) -> AsyncTask<T> {
+        let failed = self.failed.clone();
+        AsyncTask::from_future(async move {
+            let result = task.receiver.await;
+            let evict = match (&result, &failed) {
+                (Ok(value), Some(failed)) => failed(value),
+                (Ok(_), None) => false,
+                (Err(_), _) => true,
+            };
+            if evict {
+                dead.store(true, Ordering::Release);
+            }
+            match result {
+                Ok(value) => value,
+                // Re-raise the load's panic for everyone awaiting it
+                Err(_) => panic!("AsyncTask channel closed unexpectedly"),
+            }
+        })
+    }
+
+    /// Get the live entry for `key` without starting a load
+    pub fn get(&self, key: &K) -> Option<SharedTask<T>> {
+        self.lock()
+            .get(key)
+            .filter(|entry| !self.is_expired(entry))
+            .map(|entry| entry.task.clone())
+    }
+
+    /// Drop the entry for `key`, so the next request reloads it
+    pub fn invalidate(&self, key: &K) {
+        self.lock().remove(key);
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Drop expired entries, returning how many were removed
+    pub fn purge_expired(&self) -> usize {
+        let mut entries = self.lock();
+        let before = entries.len();
+        entries.retain(|_, entry| !self.is_expired(entry));
+        before - entries.len()
+    }
+
+    /// Number of entries, including expired ones not yet purged
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn is_expired(&self, entry: &Entry<T>) -> bool {
+        if entry.dead.load(Ordering::Acquire) {
+            return true;
+        }
+        match self.ttl {
+            Some(ttl) => entry.task.is_ready() && entry.started.elapsed() >= ttl,
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<K, Entry<T>>> {
+        // The map holds no invariants a panicking holder could break
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<K, T> Default for TaskCache<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: NotResult + Clone + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> Clone for TaskCache<K, T>
+where
+    T: NotResult,
+{
+    fn clone(&self) -> Self {
+        Self {
+            entries: Arc::clone(&self.entries),
+            ttl: self.ttl,
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+impl<K, T> fmt::Debug for TaskCache<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: NotResult + Clone + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskCache")
+            .field("entries", &self.len())
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting_load(loads: &Arc<AtomicUsize>) -> impl FnOnce() -> AsyncTask<usize> + use<> {
+        let loads = Arc::clone(loads);
+        move || {
+            AsyncTask::from_future(async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                loads.fetch_add(1, Ordering::SeqCst) + 1
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_loads_of_a_key_are_deduplicated() {
+        let cache = TaskCache::new();
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let first = cache.get_or_load("doc", counting_load(&loads));
+        let second = cache.get_or_load("doc", counting_load(&loads));
+        assert!(first.ptr_eq(&second));
+        assert_eq!((first.await, second.await), (1, 1));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn completed_entries_expire_after_ttl() {
+        let cache = TaskCache::with_ttl(Duration::from_secs(10));
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 1);
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 1);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panicked_and_failed_loads_are_retried() {
+        let cache = TaskCache::new().evict_when(|count: &usize| *count == 1);
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let panicked = cache.get_or_load("doc", || {
+            AsyncTask::from_future(async { panic!("backend down") })
+        });
+        assert!(tokio::spawn(panicked).await.is_err());
+        assert!(cache.get(&"doc").is_none());
+
+        // The first count is treated as a failure and evicted, the second kept
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 1);
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 2);
+        assert_eq!(cache.get_or_load("doc", counting_load(&loads)).await, 2);
+    }
+}
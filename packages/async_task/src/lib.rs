@@ -1,7 +1,17 @@
 #![feature(auto_traits, negative_impls)]
 
+pub mod cache;
 pub mod future_ext;
+pub mod limit;
+pub mod runtime;
+pub mod scope;
+pub mod shared;
 pub mod task;
+mod trace;
 
+pub use cache::TaskCache;
 pub use future_ext::*;
-pub use task::{AsyncTask, NotResult};
+pub use limit::{Acquire, ConcurrencyLimiter, KeyedLimiter, Limiter, RateLimiter};
+pub use scope::{Scope, ScopeError, ScopeFuture, scope};
+pub use shared::SharedTask;
+pub use task::{AsyncTask, NotResult, TaskBuilder};
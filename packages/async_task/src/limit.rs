@@ -0,0 +1,427 @@
+//! Rate and concurrency limiting for AsyncTask work
+//!
+//! - [`RateLimiter`] spaces work out over time (GCRA, a token bucket that
+//!   tracks a single timestamp)
+//! - [`ConcurrencyLimiter`] caps how much work runs at once
+//! - [`KeyedLimiter`] keeps one limiter per key, e.g. per provider or registry
+//!
+//! All of them implement [`Limiter`], whose [`run`](Limiter::run) waits for a
+//! permit before starting the work:
+//!
+//! ```ignore
+//! let limiter = RateLimiter::per_second(5);
+//! let reply = limiter.run(move || client.complete(request)).await;
+//! ```
+//!
+//! Waiting happens on the Tokio clock when a runtime is current, so limits
+//! are exact under a paused test clock, and on [`runtime::sleep_until`]'s
+//! timer thread otherwise.
+
+use crate::runtime;
+use crate::task::{AsyncTask, NotResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Future resolving to a permit from a [`Limiter`]
+pub type Acquire<P> = Pin<Box<dyn Future<Output = P> + Send>>;
+
+/// Shared interface of every limiter
+///
+/// Clones of a limiter share its state, so one limiter can be handed to
+/// every task that draws on the same budget.
+pub trait Limiter: Clone + Send + Sync + 'static {
+    /// Held while the limited work runs; dropping it gives the capacity back
+    type Permit: Send + 'static;
+
+    /// Wait for a permit
+    fn acquire(&self) -> Acquire<Self::Permit>;
+
+    /// Run `f` once a permit is available, holding the permit until it finishes
+    ///
+    /// `f` is not called until the permit is granted.
+    #[track_caller]
+    fn run<F, Fut, T>(&self, f: F) -> AsyncTask<T>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: NotResult + Send + 'static,
+    {
+        let permit = self.acquire();
+        AsyncTask::from_future(async move {
+            let _permit = permit.await;
+            f().await
+        })
+    }
+}
+
+/// GCRA state shared by clones of a [`RateLimiter`]
+struct RateState {
+    /// Theoretical arrival time of the next request
+    tat: Mutex<Option<Instant>>,
+    /// Time between permits at the sustained rate
+    interval: Duration,
+    /// How far ahead of the sustained rate a burst may run
+    tolerance: Duration,
+}
+
+/// Limits how often work may start
+///
+/// Up to `burst` permits are granted at once; after that, one permit per
+/// `period / limit`. Waiters are served in the order they called
+/// [`acquire`](Limiter::acquire): each call reserves the next free slot, so
+/// a dropped acquire future still uses up its slot.
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: Arc<RateState>,
+}
+
+impl RateLimiter {
+    /// Allow `limit` permits per `period`, all of which may be used in a burst
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        assert!(limit > 0, "RateLimiter limit must be at least 1");
+        Self::with_interval(period / limit, limit)
+    }
+
+    /// Allow `limit` permits per second
+    pub fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    /// Allow `limit` permits per minute
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    /// Change how many permits may be granted back to back
+    ///
+    /// A burst of 1 spaces every permit out evenly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `burst` is zero.
+    pub fn with_burst(self, burst: u32) -> Self {
+        assert!(burst > 0, "RateLimiter burst must be at least 1");
+        Self::with_interval(self.state.interval, burst)
+    }
+
+    fn with_interval(interval: Duration, burst: u32) -> Self {
+        Self {
+            state: Arc::new(RateState {
+                tat: Mutex::new(None),
+                interval,
+                tolerance: interval * (burst - 1),
+            }),
+        }
+    }
+
+    /// Time between permits at the sustained rate
+    pub fn interval(&self) -> Duration {
+        self.state.interval
+    }
+
+    /// Take a permit if one is available right now
+    pub fn try_acquire(&self) -> bool {
+        let now = Instant::now();
+        let mut tat = self.lock();
+        let next = tat.map_or(now, |tat| tat.max(now));
+        if next.saturating_duration_since(now) > self.state.tolerance {
+            return false;
+        }
+        *tat = Some(next + self.state.interval);
+        true
+    }
+
+    /// Reserve the next slot and return when it opens
+    fn reserve(&self) -> Instant {
+        let now = Instant::now();
+        let mut tat = self.lock();
+        let next = tat.map_or(now, |tat| tat.max(now));
+        *tat = Some(next + self.state.interval);
+        next.checked_sub(self.state.tolerance)
+            .map_or(now, |at| at.max(now))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.state
+            .tat
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Limiter for RateLimiter {
+    type Permit = ();
+
+    fn acquire(&self) -> Acquire<()> {
+        let at = self.reserve();
+        runtime::sleep_until(at)
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("interval", &self.state.interval)
+            .field("tolerance", &self.state.tolerance)
+            .finish()
+    }
+}
+
+/// Limits how much work runs at the same time
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl ConcurrencyLimiter {
+    /// Allow at most `max` permits to be held at once
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Maximum number of permits
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Permits not currently held
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Take a permit if one is free right now
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.semaphore).try_acquire_owned().ok()
+    }
+}
+
+impl Limiter for ConcurrencyLimiter {
+    type Permit = OwnedSemaphorePermit;
+
+    fn acquire(&self) -> Acquire<OwnedSemaphorePermit> {
+        let semaphore = Arc::clone(&self.semaphore);
+        Box::pin(async move {
+            semaphore
+                .acquire_owned()
+                .await
+                .expect("ConcurrencyLimiter semaphore is never closed")
+        })
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimiter")
+            .field("max", &self.max)
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+type MakeLimiter<K, L> = Arc<dyn Fn(&K) -> L + Send + Sync>;
+
+/// A key's limiter and when it was last asked for
+struct Keyed<L> {
+    limiter: L,
+    used: Instant,
+}
+
+/// One limiter per key, created on first use
+///
+/// ```ignore
+/// let registries = KeyedLimiter::new(|_registry: &String| RateLimiter::per_minute(1));
+/// registries.run("crates-io".to_string(), move || publish(package)).await;
+/// ```
+///
+/// Keys are kept until [`evict_idle`](Self::evict_idle) is set, so without
+/// it the key space must be bounded, e.g. providers rather than user IDs.
+pub struct KeyedLimiter<K, L> {
+    limiters: Arc<Mutex<HashMap<K, Keyed<L>>>>,
+    make: MakeLimiter<K, L>,
+    idle: Option<Duration>,
+}
+
+impl<K, L> Clone for KeyedLimiter<K, L> {
+    fn clone(&self) -> Self {
+        Self {
+            limiters: Arc::clone(&self.limiters),
+            make: Arc::clone(&self.make),
+            idle: self.idle,
+        }
+    }
+}
+
+impl<K, L> KeyedLimiter<K, L>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    L: Limiter,
+{
+    /// Create keyed limiters, building each key's limiter with `make`
+    pub fn new(make: impl Fn(&K) -> L + Send + Sync + 'static) -> Self {
+        Self {
+            limiters: Arc::new(Mutex::new(HashMap::new())),
+            make: Arc::new(make),
+            idle: None,
+        }
+    }
+
+    /// Forget a key's limiter once it has not been asked for in `idle`
+    ///
+    /// Idle keys are dropped whenever a new key is added, so the map only
+    /// holds recently used keys. A key used again gets a fresh limiter, so
+    /// `idle` should outlast any permit still held or reserved: longer than
+    /// the longest limited work, and than a rate limiter's queue takes to
+    /// drain.
+    pub fn evict_idle(mut self, idle: Duration) -> Self {
+        self.idle = Some(idle);
+        self
+    }
+
+    /// The limiter for `key`
+    pub fn get(&self, key: &K) -> L {
+        let now = Instant::now();
+        let mut limiters = self
+            .limiters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(idle) = self.idle
+            && !limiters.contains_key(key)
+        {
+            limiters.retain(|_, keyed| now.saturating_duration_since(keyed.used) < idle);
+        }
+        let keyed = limiters.entry(key.clone()).or_insert_with(|| Keyed {
+            limiter: (self.make)(key),
+            used: now,
+        });
+        keyed.used = now;
+        keyed.limiter.clone()
+    }
+
+    /// Wait for a permit from the limiter for `key`
+    pub fn acquire(&self, key: &K) -> Acquire<L::Permit> {
+        self.get(key).acquire()
+    }
+
+    /// Run `f` under the limiter for `key`
+    #[track_caller]
+    pub fn run<F, Fut, T>(&self, key: &K, f: F) -> AsyncTask<T>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: NotResult + Send + 'static,
+    {
+        self.get(key).run(f)
+    }
+
+    /// Number of keys with a limiter
+    pub fn len(&self) -> usize {
+        self.limiters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+
+    /// Returns true if no key has been used yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, L> fmt::Debug for KeyedLimiter<K, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.limiters.lock().map_or(0, |limiters| limiters.len());
+        f.debug_struct("KeyedLimiter")
+            .field("keys", &keys)
+            .field("idle", &self.idle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_allows_a_burst_then_spaces_permits() {
+        let limiter = RateLimiter::per_second(2);
+        let started = Instant::now();
+        let mut granted = Vec::new();
+        for _ in 0..5 {
+            limiter.acquire().await;
+            granted.push(started.elapsed().as_millis());
+        }
+        assert_eq!(granted, vec![0, 0, 500, 1000, 1500]);
+        assert!(!limiter.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_limiter_caps_running_work() {
+        let limiter = ConcurrencyLimiter::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..6)
+            .map(|_| {
+                let running = Arc::clone(&running);
+                let peak = Arc::clone(&peak);
+                limiter.run(move || async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        let started = Instant::now();
+        for task in tasks {
+            task.await;
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyed_limiters_are_independent() {
+        let limiters = KeyedLimiter::new(|_: &&str| RateLimiter::per_second(1));
+        limiters.acquire(&"openai").await;
+        limiters.acquire(&"anthropic").await;
+        assert!(!limiters.get(&"openai").try_acquire());
+        assert_eq!(limiters.len(), 2);
+
+        let started = Instant::now();
+        limiters.acquire(&"openai").await;
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_keys_are_evicted() {
+        let limiters = KeyedLimiter::new(|_: &u32| RateLimiter::per_second(1))
+            .evict_idle(Duration::from_secs(10));
+        limiters.acquire(&1).await;
+        limiters.acquire(&2).await;
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        limiters.acquire(&2).await;
+        tokio::time::advance(Duration::from_secs(6)).await;
+        limiters.acquire(&3).await;
+        // 1 was idle for 12s; 2 was used 6s ago
+        assert_eq!(limiters.len(), 2);
+    }
+}
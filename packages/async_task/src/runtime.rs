@@ -0,0 +1,200 @@
+//! Runtime bridging for synchronous callers
+//!
+//! AsyncTask and AsyncStream producers normally run on the caller's Tokio
+//! runtime. Synchronous code (CLI tools, plain `#[test]` functions) has no
+//! runtime, so work spawned there lands on a lazily created single-threaded
+//! fallback runtime that is driven whenever [`block_on`] waits on it.
+//!
+//! [`sleep`] and [`sleep_until`] wait without needing a Tokio runtime at all,
+//! for code polled by another executor such as the std or crossbeam stream
+//! backends.
+
+use crate::trace;
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Panic message for blocking on a thread that is driving a current-thread runtime
+const NESTED_RUNTIME_PANIC: &str = "cannot block on an AsyncTask or AsyncStream from within a \
+    current-thread Tokio runtime: the runtime could never make progress and the call would \
+    deadlock. Use `.await` instead, or switch to a multi-threaded runtime";
+
+/// Single-threaded runtime used when no Tokio runtime is running
+fn fallback_runtime() -> &'static Runtime {
+    static FALLBACK: OnceLock<Runtime> = OnceLock::new();
+    FALLBACK.get_or_init(|| {
+        Builder::new_current_thread()
+            .enable_all()
+            .thread_name("sugars-fallback-runtime")
+            .build()
+            .expect("failed to build fallback Tokio runtime")
+    })
+}
+
+/// Handle of the running runtime, or of the fallback runtime outside one
+fn handle() -> Handle {
+    Handle::try_current().unwrap_or_else(|_| fallback_runtime().handle().clone())
+}
+
+/// Spawn a future on the current runtime, or on the fallback runtime
+#[track_caller]
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawn_named(None, future)
+}
+
+/// Spawn a future under an optional task name
+///
+/// With the `tracing` feature the future runs in a span carrying the name and
+/// caller location, parented to the caller's current span. Builds with
+/// `--cfg tokio_unstable` also pass the name on to tokio-console.
+#[track_caller]
+pub fn spawn_named<F>(name: Option<&str>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let handle = handle();
+    let future = trace::instrument_future(name, Location::caller(), future);
+    #[cfg(all(feature = "tracing", tokio_unstable))]
+    if let Some(name) = name {
+        return tokio::task::Builder::new()
+            .name(name)
+            .spawn_on(future, &handle)
+            .expect("failed to spawn named task");
+    }
+    handle.spawn(future)
+}
+
+/// Run a blocking closure on the current runtime's blocking pool, or the fallback's
+#[track_caller]
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    spawn_blocking_named(None, f)
+}
+
+/// Run a blocking closure under an optional task name
+///
+/// Traced the same way as [`spawn_named`].
+#[track_caller]
+pub fn spawn_blocking_named<F, R>(name: Option<&str>, f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let handle = handle();
+    let f = trace::instrument_blocking(name, Location::caller(), f);
+    #[cfg(all(feature = "tracing", tokio_unstable))]
+    if let Some(name) = name {
+        return tokio::task::Builder::new()
+            .name(name)
+            .spawn_blocking_on(f, &handle)
+            .expect("failed to spawn named blocking task");
+    }
+    handle.spawn_blocking(f)
+}
+
+/// Block the current thread until the future completes
+///
+/// - Outside any runtime, the future is driven on the fallback runtime, which
+///   also runs everything spawned through [`spawn`] in the meantime.
+/// - Inside a multi-threaded runtime, the worker is handed off with
+///   `block_in_place` so other tasks keep running.
+///
+/// # Panics
+///
+/// Panics with an explanatory message when called on a thread owned by a
+/// current-thread runtime (such as the body of a default `#[tokio::test]`),
+/// where blocking would deadlock.
+#[track_caller]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match Handle::try_current() {
+        Err(_) => fallback_runtime().block_on(future),
+        Ok(handle) => match handle.runtime_flavor() {
+            RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(move || handle.block_on(future))
+            }
+            _ => panic!("{NESTED_RUNTIME_PANIC}"),
+        },
+    }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`]
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Wait for `duration`; see [`sleep_until`]
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Wait until `deadline`, with or without a Tokio runtime
+///
+/// On a Tokio runtime this is a Tokio timer, so it follows a paused test
+/// clock. Anywhere else a helper thread sleeps and wakes the waiting task.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    if Handle::try_current().is_ok() {
+        return Box::pin(tokio::time::sleep_until(deadline));
+    }
+    let (done, wait) = tokio::sync::oneshot::channel::<()>();
+    let deadline = deadline.into_std();
+    std::thread::Builder::new()
+        .name("sugars-timer".into())
+        .spawn(move || {
+            std::thread::sleep(deadline.saturating_duration_since(std::time::Instant::now()));
+            let _ = done.send(());
+        })
+        .expect("failed to spawn timer thread");
+    Box::pin(async move {
+        let _ = wait.await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AsyncTask;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn block_on_without_runtime_drives_spawned_work() {
+        let task = AsyncTask::from_future(async { 21 * 2 });
+        assert_eq!(task.block_on(), 42);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn block_on_inside_multi_thread_runtime() {
+        let task = AsyncTask::from_future(async { "done" });
+        assert_eq!(task.block_on(), "done");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn named_tasks_run_like_unnamed_ones() {
+        let task = AsyncTask::named("answer").from_future(async { 42 });
+        assert_eq!(task.await, 42);
+        let task = AsyncTask::named("blocking answer").spawn(|| 42);
+        assert_eq!(task.await, 42);
+    }
+
+    #[test]
+    fn sleep_works_outside_tokio() {
+        let started = Instant::now();
+        futures::executor::block_on(super::sleep(Duration::from_millis(20)));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "current-thread Tokio runtime")]
+    async fn block_on_inside_current_thread_runtime_panics() {
+        AsyncTask::from_value(1).block_on();
+    }
+}
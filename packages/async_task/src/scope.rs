@@ -0,0 +1,278 @@
+//! Structured concurrency for spawned work
+//!
+//! [`scope`] runs a body that spawns children through a [`Scope`] handle.
+//! The returned [`ScopeFuture`] resolves only once the body and every child
+//! have finished, so nothing outlives the scope:
+//!
+//! - children's values are returned in spawn order as a `ZeroOneOrMany<T>`
+//! - the first child to fail (or panic) aborts all of its siblings
+//! - dropping the `ScopeFuture` aborts every child still running
+//!
+//! ```ignore
+//! let results = sugars_async_task::scope(|s| async move {
+//!     for package in tier {
+//!         s.spawn(publish(package));
+//!     }
+//! })
+//! .await?;
+//! ```
+
+use crate::runtime;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::any::Any;
+use std::fmt;
+use std::future::{Future, poll_fn};
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use sugars_collections::ZeroOneOrMany;
+use tokio::sync::mpsc;
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+
+/// Why a scope did not complete
+#[derive(Debug)]
+pub enum ScopeError<E> {
+    /// A child returned an error; its siblings were aborted
+    Failed(E),
+    /// A child panicked; its siblings were aborted
+    Panicked(String),
+}
+
+impl<E: fmt::Display> fmt::Display for ScopeError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeError::Failed(error) => write!(f, "scoped task failed: {error}"),
+            ScopeError::Panicked(message) => write!(f, "scoped task panicked: {message}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for ScopeError<E> {}
+
+/// A spawned child and its position in spawn order
+type Child<T, E> = (usize, JoinHandle<Result<T, E>>);
+
+/// A child being joined, tagged with its position in spawn order
+type Join<T, E> = Pin<Box<dyn Future<Output = (usize, Result<Result<T, E>, JoinError>)> + Send>>;
+
+type ScopeOutput<T, E> = Result<ZeroOneOrMany<T>, ScopeError<E>>;
+
+/// Handle for spawning children into a scope
+///
+/// Clones spawn into the same scope, so children can spawn grandchildren.
+pub struct Scope<T, E> {
+    sender: mpsc::UnboundedSender<Child<T, E>>,
+    next_index: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T, E> Clone for Scope<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            next_index: Arc::clone(&self.next_index),
+            cancelled: Arc::clone(&self.cancelled),
+        }
+    }
+}
+
+impl<T, E> Scope<T, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    /// Spawn a child whose value is collected by the scope
+    ///
+    /// Returning `Err` fails the whole scope. Spawning after the scope has
+    /// already failed is a no-op.
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        if self.is_cancelled() {
+            return;
+        }
+        let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        let handle = runtime::spawn(future);
+        if let Err(rejected) = self.sender.send((index, handle)) {
+            // The scope is no longer joining children
+            rejected.0.1.abort();
+        }
+    }
+
+    /// Returns true once a child has failed and the scope is shutting down
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// Future returned by [`scope`]
+///
+/// Resolves to every child's value in spawn order, or to the first failure.
+pub struct ScopeFuture<T, E> {
+    inner: Pin<Box<dyn Future<Output = ScopeOutput<T, E>> + Send>>,
+}
+
+impl<T, E> Future for ScopeFuture<T, E> {
+    type Output = ScopeOutput<T, E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// Children still running, aborted if the scope is dropped or fails
+struct Running<T, E> {
+    joins: FuturesUnordered<Join<T, E>>,
+    aborts: Vec<AbortHandle>,
+}
+
+impl<T, E> Running<T, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    fn push(&mut self, (index, handle): Child<T, E>) {
+        self.aborts.push(handle.abort_handle());
+        self.joins
+            .push(Box::pin(async move { (index, handle.await) }));
+    }
+}
+
+impl<T, E> Drop for Running<T, E> {
+    fn drop(&mut self) {
+        for abort in &self.aborts {
+            abort.abort();
+        }
+    }
+}
+
+/// Run `body` with a [`Scope`] and join everything it spawns
+///
+/// See the [module docs](self) for the cancellation rules.
+pub fn scope<T, E, F, Fut>(body: F) -> ScopeFuture<T, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: FnOnce(Scope<T, E>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let body = body(Scope {
+        sender,
+        next_index: Arc::new(AtomicUsize::new(0)),
+        cancelled: Arc::clone(&cancelled),
+    });
+
+    let inner = async move {
+        let mut body = pin!(body);
+        let mut body_done = false;
+        let mut accepting = true;
+        let mut running = Running {
+            joins: FuturesUnordered::new(),
+            aborts: Vec::new(),
+        };
+        let mut slots: Vec<Option<T>> = Vec::new();
+
+        let outcome = poll_fn(|cx| {
+            if !body_done && body.as_mut().poll(cx).is_ready() {
+                body_done = true;
+            }
+            while accepting {
+                match receiver.poll_recv(cx) {
+                    Poll::Ready(Some(child)) => running.push(child),
+                    Poll::Ready(None) => accepting = false,
+                    Poll::Pending => break,
+                }
+            }
+            while let Poll::Ready(Some((index, joined))) = running.joins.poll_next_unpin(cx) {
+                match joined {
+                    Ok(Ok(value)) => {
+                        if slots.len() <= index {
+                            slots.resize_with(index + 1, || None);
+                        }
+                        slots[index] = Some(value);
+                    }
+                    Ok(Err(error)) => return Poll::Ready(Err(ScopeError::Failed(error))),
+                    Err(error) if error.is_panic() => {
+                        return Poll::Ready(Err(ScopeError::Panicked(panic_message(
+                            error.into_panic(),
+                        ))));
+                    }
+                    // Aborted from outside the scope; nothing to collect
+                    Err(_) => {}
+                }
+            }
+            if body_done && !accepting && running.joins.is_empty() {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        if let Err(error) = outcome {
+            cancelled.store(true, Ordering::Release);
+            return Err(error);
+        }
+        Ok(ZeroOneOrMany::many(slots.into_iter().flatten().collect()))
+    };
+
+    ScopeFuture {
+        inner: Box::pin(inner),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_string(),
+            Err(_) => "non-string panic payload".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn results_come_back_in_spawn_order() {
+        let results = scope(|s| async move {
+            for (n, delay) in [(1, 30), (2, 10), (3, 20)] {
+                s.spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    Ok::<_, String>(n)
+                });
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(Vec::from(results), vec![1, 2, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failure_cancels_siblings() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let sibling_finished = Arc::clone(&finished);
+
+        let result = scope(|s| async move {
+            s.spawn(async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                sibling_finished.store(true, Ordering::SeqCst);
+                Ok(())
+            });
+            s.spawn(async { Err("boom") });
+        })
+        .await;
+
+        assert!(matches!(result, Err(ScopeError::Failed("boom"))));
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert!(!finished.load(Ordering::SeqCst));
+    }
+}
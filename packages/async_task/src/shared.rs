@@ -0,0 +1,105 @@
+//! Cloneable, memoized AsyncTask results
+//!
+//! An [`AsyncTask`] has exactly one consumer. [`SharedTask`] lets any number
+//! of consumers await the same underlying task; every clone resolves to a
+//! clone of the same value and the work itself runs only once.
+
+use crate::task::{AsyncTask, NotResult};
+use futures::FutureExt;
+use futures::future::Shared;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Cloneable handle to the result of a single AsyncTask
+///
+/// Created with [`AsyncTask::shared`]. Clones are cheap and can be awaited
+/// independently, from different tasks or threads.
+pub struct SharedTask<T>
+where
+    T: NotResult, // T cannot be any Result type
+{
+    inner: Shared<AsyncTask<T>>,
+}
+
+impl<T> SharedTask<T>
+where
+    T: NotResult + Clone, // T cannot be any Result type
+{
+    /// The result, if some clone has already awaited it to completion
+    pub fn peek(&self) -> Option<&T> {
+        self.inner.peek()
+    }
+
+    /// Returns true once the result is available without waiting
+    pub fn is_ready(&self) -> bool {
+        self.peek().is_some()
+    }
+
+    /// Returns true if both handles share the same underlying task
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+}
+
+impl<T> AsyncTask<T>
+where
+    T: NotResult + Clone, // T cannot be any Result type
+{
+    /// Turn this task into a cloneable handle that many consumers can await
+    pub fn shared(self) -> SharedTask<T> {
+        SharedTask {
+            inner: FutureExt::shared(self),
+        }
+    }
+}
+
+impl<T> Clone for SharedTask<T>
+where
+    T: NotResult, // T cannot be any Result type
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Future for SharedTask<T>
+where
+    T: NotResult + Clone, // T cannot be any Result type
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+impl<T> fmt::Debug for SharedTask<T>
+where
+    T: NotResult + Clone + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedTask")
+            .field("result", &self.peek())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AsyncTask;
+
+    #[tokio::test]
+    async fn every_clone_resolves_to_the_same_value() {
+        let task = AsyncTask::from_future(async { String::from("document") }).shared();
+        let other = task.clone();
+
+        let waiter = tokio::spawn(other);
+        assert_eq!(task.clone().await, "document");
+        assert_eq!(waiter.await.unwrap(), "document");
+        assert_eq!(task.peek().map(String::as_str), Some("document"));
+    }
+}
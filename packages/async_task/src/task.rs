@@ -3,7 +3,9 @@
 //! This module provides reusable async primitives that follow the project's
 //! conventions of returning concrete types instead of boxed futures or async fn.
 
+use crate::{runtime, trace};
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use sugars_collections::ZeroOneOrMany;
@@ -63,15 +65,30 @@ where
     }
 
     /// Create an AsyncTask from a future
+    ///
+    /// The future runs on the current runtime, or on the fallback runtime from
+    /// [`runtime`] when called outside one.
+    #[track_caller]
     pub fn from_future<F>(future: F) -> Self
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        Self::from_future_named(None, future)
+    }
+
+    #[track_caller]
+    fn from_future_named<F>(name: Option<&str>, future: F) -> Self
     where
         F: Future<Output = T> + Send + 'static,
         T: Send + 'static,
     {
         let (tx, rx) = oneshot::channel();
-        tokio::spawn(async move {
+        runtime::spawn_named(name, async move {
             let result = future.await;
-            let _ = tx.send(result);
+            if tx.send(result).is_err() {
+                trace::receiver_dropped();
+            }
         });
         Self { receiver: rx }
     }
@@ -87,18 +104,55 @@ where
     }
 
     /// Create an AsyncTask that spawns a blocking task
+    #[track_caller]
     pub fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        Self::spawn_named(None, f)
+    }
+
+    #[track_caller]
+    fn spawn_named<F>(name: Option<&str>, f: F) -> Self
     where
         F: FnOnce() -> T + Send + 'static,
         T: Send + 'static,
     {
         let (tx, rx) = oneshot::channel();
-        tokio::task::spawn_blocking(move || {
+        runtime::spawn_blocking_named(name, move || {
             let result = f();
-            let _ = tx.send(result);
+            if tx.send(result).is_err() {
+                trace::receiver_dropped();
+            }
         });
         Self { receiver: rx }
     }
+
+    /// Start building a named task
+    ///
+    /// The name shows up in tracing spans (with the `tracing` feature) and in
+    /// tokio-console when built with `--cfg tokio_unstable`.
+    pub fn named(name: impl Into<String>) -> TaskBuilder<T> {
+        TaskBuilder {
+            name: name.into(),
+            _output: PhantomData,
+        }
+    }
+
+    /// Block the current thread until the task completes
+    ///
+    /// Works from synchronous code with or without a running runtime; see
+    /// [`runtime::block_on`] for how each case is handled.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called from a thread driving a current-thread Tokio runtime,
+    /// where waiting would deadlock.
+    #[track_caller]
+    pub fn block_on(self) -> T {
+        runtime::block_on(self)
+    }
 }
 
 impl<T> Future for AsyncTask<T>
@@ -115,3 +169,32 @@ where
         }
     }
 }
+
+/// Builder for a named AsyncTask, created by [`AsyncTask::named`]
+pub struct TaskBuilder<T> {
+    name: String,
+    _output: PhantomData<fn() -> T>,
+}
+
+impl<T> TaskBuilder<T>
+where
+    T: NotResult + Send + 'static, // T cannot be any Result type
+{
+    /// Create the named AsyncTask from a future
+    #[track_caller]
+    pub fn from_future<F>(self, future: F) -> AsyncTask<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        AsyncTask::from_future_named(Some(&self.name), future)
+    }
+
+    /// Create the named AsyncTask from a blocking closure
+    #[track_caller]
+    pub fn spawn<F>(self, f: F) -> AsyncTask<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        AsyncTask::spawn_named(Some(&self.name), f)
+    }
+}
@@ -0,0 +1,93 @@
+//! Tracing hooks for spawned tasks
+//!
+//! Everything here compiles to nothing unless the `tracing` feature is on.
+//! With it, each spawned task runs in an `async_task` span parented to the
+//! span that was current at the spawn site, carrying the task name, the
+//! `#[track_caller]` location and, once finished, its duration.
+
+use std::future::Future;
+use std::panic::Location;
+
+/// Name recorded for tasks spawned without [`AsyncTask::named`](crate::AsyncTask::named)
+#[cfg(feature = "tracing")]
+const UNNAMED: &str = "unnamed";
+
+#[cfg(feature = "tracing")]
+fn task_span(name: Option<&str>, location: &'static Location<'static>) -> tracing::Span {
+    tracing::debug_span!(
+        "async_task",
+        task.name = name.unwrap_or(UNNAMED),
+        caller = %location,
+        duration_ms = tracing::field::Empty,
+    )
+}
+
+/// Run `future` inside a task span that records its duration
+#[cfg(feature = "tracing")]
+pub(crate) fn instrument_future<F: Future>(
+    name: Option<&str>,
+    location: &'static Location<'static>,
+    future: F,
+) -> impl Future<Output = F::Output> + use<F> {
+    use tracing::Instrument;
+
+    let span = task_span(name, location);
+    let timed = span.clone();
+    async move {
+        let started = std::time::Instant::now();
+        let output = future.await;
+        timed.record("duration_ms", started.elapsed().as_millis() as u64);
+        output
+    }
+    .instrument(span)
+}
+
+/// Pass-through when tracing is disabled
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn instrument_future<F: Future>(
+    _name: Option<&str>,
+    _location: &'static Location<'static>,
+    future: F,
+) -> F {
+    future
+}
+
+/// Run blocking closure `f` inside a task span that records its duration
+#[cfg(feature = "tracing")]
+pub(crate) fn instrument_blocking<F, R>(
+    name: Option<&str>,
+    location: &'static Location<'static>,
+    f: F,
+) -> impl FnOnce() -> R + use<F, R>
+where
+    F: FnOnce() -> R,
+{
+    let span = task_span(name, location);
+    move || {
+        let _entered = span.enter();
+        let started = std::time::Instant::now();
+        let output = f();
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        output
+    }
+}
+
+/// Pass-through when tracing is disabled
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn instrument_blocking<F, R>(
+    _name: Option<&str>,
+    _location: &'static Location<'static>,
+    f: F,
+) -> F
+where
+    F: FnOnce() -> R,
+{
+    f
+}
+
+/// Record that an AsyncTask was dropped before its result was delivered
+#[inline]
+pub(crate) fn receiver_dropped() {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("AsyncTask dropped before its result was delivered");
+}
@@ -0,0 +1,405 @@
+//! Async builder execution pipeline
+//!
+//! An [`AsyncBuilder`] knows how to make one attempt at building its value
+//! and, optionally, how to check itself first. [`Pipeline`] runs it:
+//!
+//! 1. pre-build hooks, once, with mutable access to the builder
+//! 2. the builder's own [`validate`](AsyncBuilder::validate), then any
+//!    extra async checks
+//! 3. the build attempt
+//! 4. post-build hooks, once, on the built value
+//!
+//! Steps 2 and 3 are retried with backoff when they fail with a
+//! [`BuildError::Transient`] error and a [`Retry`] policy allows it.
+//!
+//! ```ignore
+//! let db = Database::connect(|| hash_map! { "host" => "db.internal" })
+//!     .pipeline()
+//!     .before(|db| {
+//!         db.user = env::var("DB_USER").map_err(|e| BuildError::failed(e.to_string()))?;
+//!         Ok(())
+//!     })
+//!     .retry(Retry::attempts(5))
+//!     .after(|db| log_connected(db.host.clone()))
+//!     .execute()
+//!     .await
+//!     .into_result()?;
+//! ```
+
+use crate::validation::ValidationErrors;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use sugars_async_stream::AsyncResult;
+use sugars_async_task::AsyncTask;
+
+/// Future returned by [`AsyncBuilder`] steps
+pub type BuildFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, BuildError>> + Send + 'a>>;
+
+/// Why an async build failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// A required field was never set
+    MissingField(&'static str),
+    /// The builder failed validation
+    Invalid(ValidationErrors),
+    /// A failure worth retrying, e.g. a timeout or DNS hiccup
+    Transient(String),
+    /// Any other failure, including from a hook
+    Failed(String),
+}
+
+impl BuildError {
+    /// A failure worth retrying
+    pub fn transient(message: impl Into<String>) -> Self {
+        BuildError::Transient(message.into())
+    }
+
+    /// A failure not worth retrying
+    pub fn failed(message: impl Into<String>) -> Self {
+        BuildError::Failed(message.into())
+    }
+
+    /// Returns true if a [`Retry`] policy may try again after this error
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BuildError::Transient(_))
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingField(field) => write!(f, "missing required field `{field}`"),
+            BuildError::Invalid(errors) => write!(f, "validation failed:\n{errors}"),
+            BuildError::Transient(message) => write!(f, "{message} (transient)"),
+            BuildError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Invalid(errors) => Some(errors),
+            _ => None,
+        }
+    }
+}
+
+impl From<ValidationErrors> for BuildError {
+    fn from(errors: ValidationErrors) -> Self {
+        BuildError::Invalid(errors)
+    }
+}
+
+/// How often to retry transient failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Retry {
+    /// Make at most `max_attempts` attempts in total, doubling the wait
+    /// between them from 100ms up to 10s
+    pub fn attempts(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    /// Never retry
+    pub fn never() -> Self {
+        Self::attempts(1)
+    }
+
+    /// Wait before the first retry; later waits double
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Upper bound for the wait between attempts
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Wait after failed attempt number `attempt` (starting at 1)
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+/// Trait for builders that build asynchronously
+///
+/// Implementors provide [`attempt`](Self::attempt) and may override
+/// [`validate`](Self::validate); both can run more than once when a retry
+/// policy is set, so they take `&self`.
+pub trait AsyncBuilder<T>: Sized + Send + Sync + 'static
+where
+    T: Send + 'static,
+{
+    /// Make one attempt at building the value
+    fn attempt(&self) -> BuildFuture<'_, T>;
+
+    /// Check the builder before each attempt, e.g. that a file exists
+    fn validate(&self) -> BuildFuture<'_, ()> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    /// Wrap in a [`Pipeline`] to add hooks, checks or retries
+    fn pipeline(self) -> Pipeline<Self, T> {
+        Pipeline::new(self)
+    }
+
+    /// Validate and build, without hooks or retries
+    fn execute(self) -> AsyncTask<AsyncResult<T, BuildError>> {
+        self.pipeline().execute()
+    }
+}
+
+type Before<B> = Box<dyn FnOnce(&mut B) -> Result<(), BuildError> + Send>;
+type Check<B> = Box<dyn Fn(&B) -> BuildFuture<'static, ()> + Send + Sync>;
+type After<T> = Box<dyn FnOnce(&T) -> BuildFuture<'static, ()> + Send>;
+
+/// Runs an [`AsyncBuilder`] with hooks, checks and retries
+pub struct Pipeline<B, T> {
+    builder: B,
+    before: Vec<Before<B>>,
+    checks: Vec<Check<B>>,
+    after: Vec<After<T>>,
+    retry: Retry,
+}
+
+impl<B, T> Pipeline<B, T>
+where
+    B: AsyncBuilder<T>,
+    T: Send + 'static,
+{
+    /// Start a pipeline with no hooks and no retries
+    pub fn new(builder: B) -> Self {
+        Self {
+            builder,
+            before: Vec::new(),
+            checks: Vec::new(),
+            after: Vec::new(),
+            retry: Retry::never(),
+        }
+    }
+
+    /// Run `hook` on the builder before validation, in the order added
+    pub fn before<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&mut B) -> Result<(), BuildError> + Send + 'static,
+    {
+        self.before.push(Box::new(hook));
+        self
+    }
+
+    /// Add an async check that runs after the builder's own validation
+    ///
+    /// The returned future must own what it needs, so clone fields out of
+    /// the builder before the `async` block.
+    pub fn check<F, Fut>(mut self, check: F) -> Self
+    where
+        F: Fn(&B) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), BuildError>> + Send + 'static,
+    {
+        self.checks
+            .push(Box::new(move |builder| Box::pin(check(builder))));
+        self
+    }
+
+    /// Run `hook` on the built value, in the order added
+    pub fn after<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&T) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), BuildError>> + Send + 'static,
+    {
+        self.after
+            .push(Box::new(move |value| Box::pin(hook(value))));
+        self
+    }
+
+    /// Retry validation and the build attempt on transient failures
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Run the pipeline
+    pub fn execute(self) -> AsyncTask<AsyncResult<T, BuildError>> {
+        AsyncTask::from_future(async move { self.run().await.into() })
+    }
+
+    async fn run(self) -> Result<T, BuildError> {
+        let Pipeline {
+            mut builder,
+            before,
+            checks,
+            after,
+            retry,
+        } = self;
+        for hook in before {
+            hook(&mut builder)?;
+        }
+
+        let mut attempt = 1;
+        let value = loop {
+            match Self::attempt(&builder, &checks).await {
+                Ok(value) => break value,
+                Err(error) if error.is_transient() && attempt < retry.max_attempts => {
+                    tokio::time::sleep(retry.delay(attempt)).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        };
+
+        for hook in after {
+            hook(&value).await?;
+        }
+        Ok(value)
+    }
+
+    async fn attempt(builder: &B, checks: &[Check<B>]) -> Result<T, BuildError> {
+        builder.validate().await?;
+        for check in checks {
+            check(builder).await?;
+        }
+        builder.attempt().await
+    }
+}
+
+impl<B: fmt::Debug, T> fmt::Debug for Pipeline<B, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("builder", &self.builder)
+            .field("before", &self.before.len())
+            .field("checks", &self.checks.len())
+            .field("after", &self.after.len())
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ApiClient, Database};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Fails transiently until `succeed_on` attempts have been made
+    struct Flaky {
+        attempts: Arc<AtomicU32>,
+        succeed_on: u32,
+    }
+
+    impl AsyncBuilder<u32> for Flaky {
+        fn attempt(&self) -> BuildFuture<'_, u32> {
+            Box::pin(async move {
+                let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                if attempt < self.succeed_on {
+                    Err(BuildError::transient("connection reset"))
+                } else {
+                    Ok(attempt)
+                }
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hooks_run_around_retried_attempts() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (before, check, after) = (Arc::clone(&log), Arc::clone(&log), Arc::clone(&log));
+        let started = tokio::time::Instant::now();
+
+        let built = Flaky {
+            attempts: Arc::new(AtomicU32::new(0)),
+            succeed_on: 3,
+        }
+        .pipeline()
+        .before(move |flaky| {
+            before.lock().unwrap().push("before".to_string());
+            flaky.succeed_on = 2;
+            Ok(())
+        })
+        .check(move |_| {
+            check.lock().unwrap().push("check".to_string());
+            std::future::ready(Ok(()))
+        })
+        .after(move |value| {
+            after.lock().unwrap().push(format!("after {value}"));
+            std::future::ready(Ok(()))
+        })
+        .retry(Retry::attempts(3).backoff(Duration::from_secs(1)))
+        .execute()
+        .await;
+
+        assert_eq!(built.into_inner(), Ok(2));
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["before", "check", "check", "after 2"]
+        );
+
+        let exhausted = Flaky {
+            attempts: Arc::new(AtomicU32::new(0)),
+            succeed_on: 5,
+        }
+        .pipeline()
+        .retry(Retry::attempts(2))
+        .execute()
+        .await;
+        assert!(exhausted.into_inner().unwrap_err().is_transient());
+    }
+
+    #[tokio::test]
+    async fn reference_builders_report_build_errors() {
+        let missing = ApiClient::new().execute().await;
+        assert_eq!(
+            missing.into_inner().unwrap_err(),
+            BuildError::MissingField("endpoint")
+        );
+
+        let invalid = ApiClient::new().endpoint("api.example.com").execute().await;
+        let Err(BuildError::Invalid(errors)) = invalid.into_inner() else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(errors.iter().next().unwrap().pointer, "/endpoint");
+
+        let built = ApiClient::new()
+            .endpoint("https://api.example.com")
+            .execute()
+            .await;
+        assert_eq!(
+            built.into_inner().unwrap().endpoint,
+            "https://api.example.com"
+        );
+
+        let database = Database {
+            host: "localhost".to_string(),
+            port: "postgres".to_string(),
+            database: "app".to_string(),
+            user: "app".to_string(),
+            password: None,
+        };
+        let Err(BuildError::Invalid(errors)) = database.execute().await.into_inner() else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(errors.iter().next().unwrap().pointer, "/port");
+    }
+}
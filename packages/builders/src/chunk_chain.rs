@@ -0,0 +1,854 @@
+//! Composable middleware for streams of message chunks
+//!
+//! A [`ChunkChain`] runs every chunk of an `AsyncStream<T: MessageChunk>`
+//! through a stack of [`ChunkMiddleware`], in the order they were added.
+//! Each one may pass a chunk on, drop it, or emit extra chunks:
+//!
+//! ```ignore
+//! let text = AccumulateText::new();
+//! let reply = text.buffer();
+//! let tools = DetectToolCalls::new();
+//! let calls = tools.calls();
+//!
+//! let chain = ChunkChain::new()
+//!     .with(RetryBadChunks::new(2))
+//!     .with(text)
+//!     .with(tools)
+//!     .with(|chunk: ChatChunk| (!chunk.is_empty()).then_some(chunk));
+//!
+//! chain.run(move || agent.chat(prompt.clone())).collect().await;
+//! println!("{} ({} tool calls)", reply.get(), calls.get().len());
+//! ```
+//!
+//! Built-ins that gather something, like [`AccumulateText`], hand out a
+//! cloneable handle before they are moved into the chain; read it while the
+//! stream runs or after it ends. Any `FnMut(T) -> Option<T>` closure is a
+//! middleware too.
+
+use crate::chunk_handler::MessageChunk;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use sugars_async_stream::{NotResult, StreamBackend};
+use sugars_async_task::runtime;
+
+/// One stage of a [`ChunkChain`]
+pub trait ChunkMiddleware<T>: Send + 'static {
+    /// Handle a chunk, pushing whatever should continue down the chain
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>);
+
+    /// Called once when the source ends; may push final chunks
+    fn on_end(&mut self, emit: &mut Emit<T>) {
+        let _ = emit;
+    }
+}
+
+impl<T, F> ChunkMiddleware<T> for F
+where
+    F: FnMut(T) -> Option<T> + Send + 'static,
+{
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        if let Some(chunk) = self(chunk) {
+            emit.push(chunk);
+        }
+    }
+}
+
+/// Output of one middleware call
+pub struct Emit<T> {
+    chunks: Vec<T>,
+    can_retry: bool,
+    retry: Option<Duration>,
+}
+
+impl<T> Emit<T> {
+    fn new(can_retry: bool) -> Self {
+        Self {
+            chunks: Vec::new(),
+            can_retry,
+            retry: None,
+        }
+    }
+
+    /// Pass a chunk to the next middleware
+    pub fn push(&mut self, chunk: T) {
+        self.chunks.push(chunk);
+    }
+
+    /// Whether the source can be restarted: the chain was started with
+    /// [`ChunkChain::run`] and this is its first middleware
+    ///
+    /// Only the first middleware sees a chunk before any other has acted
+    /// on it, so only it may turn that chunk into a restart.
+    pub fn can_retry(&self) -> bool {
+        self.can_retry
+    }
+
+    /// Ask for the source to be restarted after this chunk
+    ///
+    /// Returns false, and does nothing, if the source cannot be restarted.
+    pub fn retry(&mut self) -> bool {
+        self.retry_after(Duration::ZERO)
+    }
+
+    /// Like [`retry`](Self::retry), but wait `delay` before restarting
+    pub fn retry_after(&mut self, delay: Duration) -> bool {
+        if self.can_retry {
+            self.retry = Some(self.retry.map_or(delay, |longest| longest.max(delay)));
+        }
+        self.can_retry
+    }
+}
+
+/// A stack of [`ChunkMiddleware`] to run a chunk stream through
+pub struct ChunkChain<T> {
+    layers: Vec<Box<dyn ChunkMiddleware<T>>>,
+}
+
+impl<T> ChunkChain<T>
+where
+    T: MessageChunk + NotResult + Send + 'static,
+{
+    /// An empty chain, which passes every chunk through
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Add a middleware after those already in the chain
+    pub fn with(mut self, middleware: impl ChunkMiddleware<T>) -> Self {
+        self.layers.push(Box::new(middleware));
+        self
+    }
+
+    /// Number of middleware in the chain
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns true if the chain has no middleware
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Run the chain over a stream
+    ///
+    /// The source cannot be restarted, so [`RetryBadChunks`] passes bad
+    /// chunks through; use [`run`](Self::run) to allow retries.
+    pub fn apply<S: StreamBackend<T>>(self, stream: S) -> S {
+        S::from_adapter(Chained::new(self, stream, None))
+    }
+
+    /// Run the chain over the stream `source` returns, calling it again
+    /// whenever the first middleware asks for a retry
+    pub fn run<S, F>(self, mut source: F) -> S
+    where
+        S: StreamBackend<T>,
+        F: FnMut() -> S + Send + 'static,
+    {
+        let first = source();
+        let restart: Restart<T> = Box::new(move || Box::pin(source()));
+        S::from_adapter(Chained::new(self, first, Some(restart)))
+    }
+}
+
+impl<T> Default for ChunkChain<T>
+where
+    T: MessageChunk + NotResult + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ChunkChain<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkChain")
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
+
+/// Run an [`AsyncStream`](sugars_async_stream::AsyncStream) of chunks
+/// through a [`ChunkChain`]
+pub trait ChunkStreamExt<T>: StreamBackend<T>
+where
+    T: MessageChunk + NotResult + Send + 'static,
+{
+    /// Shorthand for [`ChunkChain::apply`]
+    fn through(self, chain: ChunkChain<T>) -> Self {
+        chain.apply(self)
+    }
+}
+
+impl<T, S> ChunkStreamExt<T> for S
+where
+    T: MessageChunk + NotResult + Send + 'static,
+    S: StreamBackend<T>,
+{
+}
+
+type Source<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+type Restart<T> = Box<dyn FnMut() -> Source<T> + Send>;
+
+/// Poll adapter driving a chain; nothing is spawned
+struct Chained<T> {
+    source: Source<T>,
+    restart: Option<Restart<T>>,
+    layers: Vec<Box<dyn ChunkMiddleware<T>>>,
+    ready: VecDeque<T>,
+    retry: Option<Duration>,
+    /// Wait before the pending restart
+    backoff: Option<runtime::Sleep>,
+    done: bool,
+}
+
+impl<T: Send + 'static> Chained<T> {
+    fn new(
+        chain: ChunkChain<T>,
+        source: impl Stream<Item = T> + Send + 'static,
+        restart: Option<Restart<T>>,
+    ) -> Self {
+        Self {
+            source: Box::pin(source),
+            restart,
+            layers: chain.layers,
+            ready: VecDeque::new(),
+            retry: None,
+            backoff: None,
+            done: false,
+        }
+    }
+
+    /// Feed `chunks` through the layers from `from` on
+    fn run(&mut self, from: usize, mut chunks: Vec<T>) {
+        let can_retry = self.restart.is_some();
+        for (at, layer) in self.layers.iter_mut().enumerate().skip(from) {
+            let mut emit = Emit::new(can_retry && at == 0);
+            for chunk in chunks {
+                layer.on_chunk(chunk, &mut emit);
+            }
+            self.retry = self.retry.or(emit.retry);
+            chunks = emit.chunks;
+        }
+        self.ready.extend(chunks);
+    }
+
+    /// Let every layer flush, passing what it emits to the layers after it
+    fn end(&mut self) {
+        for at in 0..self.layers.len() {
+            let mut emit = Emit::new(false);
+            self.layers[at].on_end(&mut emit);
+            self.run(at + 1, emit.chunks);
+        }
+    }
+}
+
+impl<T> Unpin for Chained<T> {}
+
+impl<T: Send + 'static> Stream for Chained<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(chunk) = this.ready.pop_front() {
+                return Poll::Ready(Some(chunk));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(backoff) = &mut this.backoff {
+                if backoff.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.backoff = None;
+                if let Some(restart) = &mut this.restart {
+                    this.source = restart();
+                }
+            }
+            match this.source.as_mut().poll_next(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    this.run(0, vec![chunk]);
+                    match this.retry.take() {
+                        Some(delay) if !delay.is_zero() => {
+                            this.backoff = Some(runtime::sleep(delay));
+                        }
+                        Some(_) => {
+                            if let Some(restart) = &mut this.restart {
+                                this.source = restart();
+                            }
+                        }
+                        None => {}
+                    }
+                }
+                Poll::Ready(None) => {
+                    this.end();
+                    this.done = true;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+fn lock<V>(shared: &Mutex<V>) -> MutexGuard<'_, V> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Text gathered by [`AccumulateText`]
+#[derive(Debug, Clone, Default)]
+pub struct TextBuffer(Arc<Mutex<String>>);
+
+impl TextBuffer {
+    /// The text so far
+    pub fn get(&self) -> String {
+        lock(&self.0).clone()
+    }
+}
+
+/// Appends the text of every good chunk to a [`TextBuffer`]
+#[derive(Debug, Default)]
+pub struct AccumulateText {
+    buffer: TextBuffer,
+}
+
+impl AccumulateText {
+    /// Start with an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle to the accumulated text
+    pub fn buffer(&self) -> TextBuffer {
+        self.buffer.clone()
+    }
+}
+
+impl<T: MessageChunk> ChunkMiddleware<T> for AccumulateText {
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        if !chunk.is_error()
+            && let Some(text) = chunk.text()
+        {
+            lock(&self.buffer.0).push_str(text);
+        }
+        emit.push(chunk);
+    }
+}
+
+/// A tool call found in chunk text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Name of the tool
+    pub name: String,
+    /// Arguments, parsed if they were sent as a JSON string
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    /// Read a call from `{"name": .., "arguments" | "parameters" | "input": ..}`
+    fn from_value(value: Value) -> Option<Self> {
+        let Value::Object(mut object) = value else {
+            return None;
+        };
+        let Some(Value::String(name)) = object.remove("name") else {
+            return None;
+        };
+        let arguments = ["arguments", "parameters", "input"]
+            .into_iter()
+            .find_map(|key| object.remove(key))?;
+        let arguments = match arguments {
+            Value::String(json) => serde_json::from_str(&json).unwrap_or(Value::String(json)),
+            arguments => arguments,
+        };
+        Some(Self { name, arguments })
+    }
+}
+
+/// Tool calls found by [`DetectToolCalls`]
+#[derive(Debug, Clone, Default)]
+pub struct ToolCalls(Arc<Mutex<Vec<ToolCall>>>);
+
+impl ToolCalls {
+    /// Calls found so far, in stream order
+    pub fn get(&self) -> Vec<ToolCall> {
+        lock(&self.0).clone()
+    }
+
+    /// Remove and return the calls found so far
+    pub fn take(&self) -> Vec<ToolCall> {
+        std::mem::take(&mut *lock(&self.0))
+    }
+}
+
+/// Longest JSON object [`DetectToolCalls`] buffers before giving up on it
+const MAX_TOOL_CALL_LEN: usize = 64 * 1024;
+
+/// Finds JSON tool calls in chunk text, even when split across chunks
+///
+/// Any top-level `{...}` in the text is parsed once its braces balance. A
+/// `{` followed by anything but a key or `}` is taken as prose and skipped.
+/// Objects with a string `name` and an `arguments`, `parameters` or `input`
+/// field are recorded as [`ToolCall`]s; other JSON and prose are ignored.
+/// Chunks pass through unchanged.
+#[derive(Debug, Default)]
+pub struct DetectToolCalls {
+    calls: ToolCalls,
+    object: String,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Inside the outer `{`, before its first token
+    opening: bool,
+}
+
+impl DetectToolCalls {
+    /// Start with no calls found
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle to the calls found
+    pub fn calls(&self) -> ToolCalls {
+        self.calls.clone()
+    }
+
+    fn scan(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.opening && !c.is_whitespace() {
+                self.opening = false;
+                if c != '"' && c != '}' {
+                    self.reset();
+                }
+            }
+            if self.depth == 0 {
+                if c == '{' {
+                    self.depth = 1;
+                    self.opening = true;
+                    self.object.push(c);
+                }
+                continue;
+            }
+            self.object.push(c);
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => self.in_string = true,
+                '{' => self.depth += 1,
+                '}' => self.depth -= 1,
+                _ => {}
+            }
+            if self.depth == 0 {
+                let object = std::mem::take(&mut self.object);
+                if let Some(call) = serde_json::from_str(&object)
+                    .ok()
+                    .and_then(ToolCall::from_value)
+                {
+                    lock(&self.calls.0).push(call);
+                }
+            } else if self.object.len() > MAX_TOOL_CALL_LEN {
+                self.reset();
+            }
+        }
+    }
+
+    /// Give up on the object being read
+    fn reset(&mut self) {
+        self.object.clear();
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+        self.opening = false;
+    }
+}
+
+impl<T: MessageChunk> ChunkMiddleware<T> for DetectToolCalls {
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        if !chunk.is_error()
+            && let Some(text) = chunk.text()
+        {
+            self.scan(text);
+        }
+        emit.push(chunk);
+    }
+}
+
+/// Tokens counted by [`CountTokens`]
+#[derive(Debug, Clone, Default)]
+pub struct TokenCount(Arc<Mutex<usize>>);
+
+impl TokenCount {
+    /// Tokens so far
+    pub fn get(&self) -> usize {
+        *lock(&self.0)
+    }
+}
+
+/// Rough token count of `text`: one token per four characters, rounded up
+pub fn approximate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Counts the tokens in the text of good chunks
+pub struct CountTokens {
+    count: TokenCount,
+    tokenize: fn(&str) -> usize,
+}
+
+impl CountTokens {
+    /// Count with [`approximate_tokens`]
+    pub fn new() -> Self {
+        Self::with(approximate_tokens)
+    }
+
+    /// Count with a real tokenizer
+    pub fn with(tokenize: fn(&str) -> usize) -> Self {
+        Self {
+            count: TokenCount::default(),
+            tokenize,
+        }
+    }
+
+    /// Handle to the running count
+    pub fn count(&self) -> TokenCount {
+        self.count.clone()
+    }
+}
+
+impl Default for CountTokens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CountTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountTokens")
+            .field("count", &self.count.get())
+            .finish()
+    }
+}
+
+impl<T: MessageChunk> ChunkMiddleware<T> for CountTokens {
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        if !chunk.is_error()
+            && let Some(text) = chunk.text()
+        {
+            *lock(&self.count.0) += (self.tokenize)(text);
+        }
+        emit.push(chunk);
+    }
+}
+
+/// Restarts the source when it fails before producing anything
+///
+/// A bad chunk that arrives before any good one is dropped and the source
+/// restarted, up to `attempts` times. Once a good chunk has gone down the
+/// chain a restart would repeat it, so later bad chunks pass through.
+///
+/// It must be the first middleware of a chain started with
+/// [`ChunkChain::run`]; anywhere else bad chunks always pass through, since
+/// the layers before it have already acted on them.
+#[derive(Debug)]
+pub struct RetryBadChunks {
+    attempts: u32,
+    started: bool,
+    delay: Duration,
+}
+
+impl RetryBadChunks {
+    /// Allow up to `attempts` restarts, each started straight away
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            started: false,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Wait `delay` before the first restart, doubling it for each one after
+    ///
+    /// The wait needs no Tokio runtime, so it works on every stream backend.
+    pub fn backoff(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl<T: MessageChunk> ChunkMiddleware<T> for RetryBadChunks {
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        if chunk.is_error() && !self.started && self.attempts > 0 && emit.retry_after(self.delay) {
+            self.attempts -= 1;
+            self.delay = self.delay.saturating_mul(2);
+            return;
+        }
+        self.started |= !chunk.is_error();
+        emit.push(chunk);
+    }
+}
+
+/// Logs every chunk as a `tracing` event
+///
+/// Good chunks are logged at trace level, bad chunks at warn and the end of
+/// the stream at debug, all with a `stream` field naming the chain.
+#[cfg(feature = "tracing")]
+#[derive(Debug)]
+pub struct LogChunks {
+    name: String,
+    chunks: usize,
+    bad: usize,
+}
+
+#[cfg(feature = "tracing")]
+impl LogChunks {
+    /// Log under the given stream name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            chunks: 0,
+            bad: 0,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<T: MessageChunk> ChunkMiddleware<T> for LogChunks {
+    fn on_chunk(&mut self, chunk: T, emit: &mut Emit<T>) {
+        let index = self.chunks;
+        self.chunks += 1;
+        match chunk.error() {
+            Some(error) => {
+                self.bad += 1;
+                tracing::warn!(stream = %self.name, index, error, "bad chunk");
+            }
+            None => tracing::trace!(stream = %self.name, index, text = chunk.text(), "chunk"),
+        }
+        emit.push(chunk);
+    }
+
+    fn on_end(&mut self, _emit: &mut Emit<T>) {
+        tracing::debug!(
+            stream = %self.name,
+            chunks = self.chunks,
+            bad = self.bad,
+            "chunk stream ended"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use sugars_async_stream::AsyncStream;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Chunk {
+        text: String,
+        error: Option<String>,
+    }
+
+    impl MessageChunk for Chunk {
+        fn bad_chunk(error: String) -> Self {
+            Self {
+                text: String::new(),
+                error: Some(error),
+            }
+        }
+
+        fn error(&self) -> Option<&str> {
+            self.error.as_deref()
+        }
+
+        fn text(&self) -> Option<&str> {
+            Some(&self.text)
+        }
+    }
+
+    fn chunks(texts: &[&str]) -> Vec<Chunk> {
+        texts
+            .iter()
+            .map(|text| Chunk {
+                text: text.to_string(),
+                error: None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn middleware_runs_in_order_and_gathers_results() {
+        let text = AccumulateText::new();
+        let reply = text.buffer();
+        let tools = DetectToolCalls::new();
+        let calls = tools.calls();
+        let tokens = CountTokens::with(|text| text.split_whitespace().count());
+        let count = tokens.count();
+
+        let chain = ChunkChain::new()
+            .with(|chunk: Chunk| (!chunk.text.is_empty()).then_some(chunk))
+            .with(text)
+            .with(tools)
+            .with(tokens);
+        let source = AsyncStream::from_vec(chunks(&[
+            "Checking ",
+            "",
+            "the weather. {\"name\": \"weather\", \"argu",
+            "ments\": \"{\\\"city\\\": \\\"Oslo }\\\"}\"} done",
+        ]));
+
+        let out = chain.apply(source).collect_async().await;
+        assert_eq!(out.len(), 3);
+        assert!(reply.get().starts_with("Checking the weather. {"));
+        assert_eq!(
+            calls.get(),
+            [ToolCall {
+                name: "weather".into(),
+                arguments: serde_json::json!({ "city": "Oslo }" }),
+            }]
+        );
+        assert_eq!(count.get(), 11);
+    }
+
+    #[tokio::test]
+    async fn bad_chunks_restart_the_source_until_it_produces() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let started = Arc::clone(&attempts);
+        let text = AccumulateText::new();
+        let reply = text.buffer();
+
+        let chain = ChunkChain::new().with(RetryBadChunks::new(2)).with(text);
+        let out = chain
+            .run(move || {
+                let mut items = chunks(&["hello"]);
+                if started.fetch_add(1, Ordering::SeqCst) < 2 {
+                    items.insert(0, Chunk::bad_chunk("connection reset".into()));
+                }
+                items.push(Chunk::bad_chunk("late".into()));
+                AsyncStream::from_vec(items)
+            })
+            .collect_async()
+            .await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(reply.get(), "hello");
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[1].error(), Some("late"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_back_off_and_only_the_first_layer_retries() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let source = |attempts: &Arc<AtomicUsize>| {
+            let started = Arc::clone(attempts);
+            move || {
+                let mut items = chunks(&["hello"]);
+                if started.fetch_add(1, Ordering::SeqCst) < 2 {
+                    items.insert(0, Chunk::bad_chunk("connection reset".into()));
+                }
+                AsyncStream::from_vec(items)
+            }
+        };
+
+        let begun = tokio::time::Instant::now();
+        let chain = ChunkChain::new().with(RetryBadChunks::new(2).backoff(Duration::from_secs(1)));
+        let out = chain.run(source(&attempts)).collect_async().await;
+        assert_eq!(out, chunks(&["hello"]));
+        assert_eq!(begun.elapsed(), Duration::from_secs(3));
+
+        attempts.store(0, Ordering::SeqCst);
+        let chain = ChunkChain::new()
+            .with(AccumulateText::new())
+            .with(RetryBadChunks::new(2));
+        let out = chain.run(source(&attempts)).collect_async().await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(out[0].error(), Some("connection reset"));
+    }
+
+    #[test]
+    fn backoff_waits_outside_tokio() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let started = Arc::clone(&attempts);
+        let chain =
+            ChunkChain::new().with(RetryBadChunks::new(1).backoff(Duration::from_millis(10)));
+        let out: AsyncStream<Chunk> = chain.run(move || {
+            let mut items = chunks(&["hello"]);
+            if started.fetch_add(1, Ordering::SeqCst) == 0 {
+                items.insert(0, Chunk::bad_chunk("connection reset".into()));
+            }
+            AsyncStream::from_vec(items)
+        });
+        let out: Vec<_> = futures::executor::block_on_stream(out).collect();
+        assert_eq!(out, chunks(&["hello"]));
+    }
+
+    /// Holds every chunk back and joins them into one at the end
+    struct Join(Vec<Chunk>);
+
+    impl ChunkMiddleware<Chunk> for Join {
+        fn on_chunk(&mut self, chunk: Chunk, _emit: &mut Emit<Chunk>) {
+            self.0.push(chunk);
+        }
+
+        fn on_end(&mut self, emit: &mut Emit<Chunk>) {
+            let text = self.0.drain(..).map(|chunk| chunk.text).collect();
+            emit.push(Chunk { text, error: None });
+        }
+    }
+
+    #[tokio::test]
+    async fn flushed_chunks_reach_later_layers() {
+        let tools = DetectToolCalls::new();
+        let calls = tools.calls();
+        let tokens = CountTokens::new();
+        let count = tokens.count();
+
+        let chain = ChunkChain::new()
+            .with(Join(Vec::new()))
+            .with(tools)
+            .with(tokens);
+        let source = AsyncStream::from_vec(chunks(&[
+            "Costs { 5 a call. ",
+            "{\"name\": \"search\", \"input\": {\"q\": \"rust\"}}",
+        ]));
+
+        let out = chain.apply(source).collect_async().await;
+        assert_eq!(out.len(), 1);
+        assert_eq!(count.get(), approximate_tokens(&out[0].text));
+        assert_eq!(count.get(), 15);
+        assert_eq!(
+            calls.get(),
+            [ToolCall {
+                name: "search".into(),
+                arguments: serde_json::json!({ "q": "rust" }),
+            }]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn logging_passes_chunks_through() {
+        let mut items = chunks(&["a", "b"]);
+        items.push(Chunk::bad_chunk("down".into()));
+
+        let chain = ChunkChain::new().with(LogChunks::new("reply"));
+        let out = chain
+            .apply(AsyncStream::from_vec(items.clone()))
+            .collect_async()
+            .await;
+        assert_eq!(out, items);
+    }
+}
@@ -3,6 +3,9 @@
 //! This module provides traits for handling message chunks in builders:
 //! - `MessageChunk` - trait for types that can represent both success and error states
 //! - `ChunkHandler` - trait for handling streaming Results
+//!
+//! See [`chunk_chain`](crate::chunk_chain) for stacking middleware over a
+//! stream of chunks.
 
 /// Trait for message chunks that can represent both success and error states
 pub trait MessageChunk: Sized {
@@ -16,6 +19,15 @@ pub trait MessageChunk: Sized {
     fn is_error(&self) -> bool {
         self.error().is_some()
     }
+
+    /// Text content of the chunk, if it carries any
+    ///
+    /// Text-based [`ChunkMiddleware`](crate::chunk_chain::ChunkMiddleware)
+    /// such as `AccumulateText` and `DetectToolCalls` read this; the
+    /// default of `None` makes them skip the chunk.
+    fn text(&self) -> Option<&str> {
+        None
+    }
 }
 
 /// Trait for builders that can handle streaming Results by unwrapping them
@@ -0,0 +1,254 @@
+//! Structural diff between two configuration trees
+//!
+//! [`ConfigDiff::between`] compares any two serializable values field by
+//! field. Objects are compared key by key and lists item by item; each
+//! difference is reported at its JSON pointer:
+//!
+//! ```ignore
+//! let diff = ConfigDiff::between(&old, &new)?;
+//! if diff.touches("/database") {
+//!     pool.reconnect(&new.database).await;
+//! }
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// How one value differs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Present only in the new tree
+    Added(Value),
+    /// Present only in the old tree
+    Removed(Value),
+    /// Present in both with different values
+    Changed {
+        /// The old value
+        old: Value,
+        /// The new value
+        new: Value,
+    },
+}
+
+/// One difference, addressed by JSON pointer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// JSON pointer to the value, e.g. `/server/port`
+    pub pointer: String,
+    /// What changed
+    pub change: Change,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.change {
+            Change::Added(new) => write!(f, "+ {} = {new}", self.pointer),
+            Change::Removed(old) => write!(f, "- {} = {old}", self.pointer),
+            Change::Changed { old, new } => write!(f, "~ {}: {old} -> {new}", self.pointer),
+        }
+    }
+}
+
+/// Every difference between two configuration trees
+///
+/// Keys are visited in the order the tree's maps iterate them, and keys
+/// only in the new tree come after the rest of their object.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigDiff(Vec<FieldChange>);
+
+impl ConfigDiff {
+    /// Compare two serializable values
+    pub fn between<T: Serialize>(old: &T, new: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::values(
+            &serde_json::to_value(old)?,
+            &serde_json::to_value(new)?,
+        ))
+    }
+
+    /// Compare two JSON trees
+    pub fn values(old: &Value, new: &Value) -> Self {
+        let mut changes = Vec::new();
+        compare(old, new, &mut String::new(), &mut changes);
+        Self(changes)
+    }
+
+    /// Show each difference with the values found at its pointer in `old`
+    /// and `new` instead
+    ///
+    /// The differences themselves are kept. Diffing raw trees and showing
+    /// their serialized configs reports a rotated [`Secret`](crate::Secret)
+    /// without its value.
+    #[cfg(feature = "watch")]
+    pub(crate) fn shown_from(self, old: &Value, new: &Value) -> Self {
+        let shown = |tree: &Value, pointer: &str, value: Value| {
+            tree.pointer(pointer).cloned().unwrap_or(value)
+        };
+        Self(
+            self.0
+                .into_iter()
+                .map(|FieldChange { pointer, change }| {
+                    let change = match change {
+                        Change::Added(value) => Change::Added(shown(new, &pointer, value)),
+                        Change::Removed(value) => Change::Removed(shown(old, &pointer, value)),
+                        Change::Changed { old: was, new: now } => Change::Changed {
+                            old: shown(old, &pointer, was),
+                            new: shown(new, &pointer, now),
+                        },
+                    };
+                    FieldChange { pointer, change }
+                })
+                .collect(),
+        )
+    }
+
+    /// Number of differences
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the trees are equal
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the differences
+    pub fn iter(&self) -> std::slice::Iter<'_, FieldChange> {
+        self.0.iter()
+    }
+
+    /// Returns true if anything at or below `pointer` changed
+    pub fn touches(&self, pointer: &str) -> bool {
+        self.0.iter().any(|field| {
+            field
+                .pointer
+                .strip_prefix(pointer)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, field) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{field}")?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for ConfigDiff {
+    type Item = FieldChange;
+    type IntoIter = std::vec::IntoIter<FieldChange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+fn compare(old: &Value, new: &Value, pointer: &mut String, changes: &mut Vec<FieldChange>) {
+    let mut child = |key: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<_>| {
+        let len = pointer.len();
+        pointer.push('/');
+        pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+        let change = match (old, new) {
+            (Some(old), Some(new)) => {
+                compare(old, new, pointer, changes);
+                None
+            }
+            (None, Some(new)) => Some(Change::Added(new.clone())),
+            (Some(old), None) => Some(Change::Removed(old.clone())),
+            (None, None) => None,
+        };
+        if let Some(change) = change {
+            changes.push(FieldChange {
+                pointer: pointer.clone(),
+                change,
+            });
+        }
+        pointer.truncate(len);
+    };
+
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, value) in old {
+                child(key, Some(value), new.get(key), changes);
+            }
+            for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                child(key, None, Some(value), changes);
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                child(&index.to_string(), old.get(index), new.get(index), changes);
+            }
+        }
+        _ if old != new => changes.push(FieldChange {
+            pointer: pointer.clone(),
+            change: Change::Changed {
+                old: old.clone(),
+                new: new.clone(),
+            },
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn differences_are_reported_by_pointer() {
+        let old = json!({
+            "server": { "port": 8080, "hosts": ["a", "b"] },
+            "debug": true,
+        });
+        let new = json!({
+            "server": { "port": 9090, "hosts": ["a"] },
+            "log/level": "info",
+        });
+
+        let diff = ConfigDiff::values(&old, &new);
+        assert_eq!(
+            diff.to_string(),
+            [
+                "- /debug = true",
+                "- /server/hosts/1 = \"b\"",
+                "~ /server/port: 8080 -> 9090",
+                "+ /log~1level = \"info\"",
+            ]
+            .join("\n")
+        );
+        assert!(diff.touches("/server"));
+        assert!(!diff.touches("/serve"));
+        assert!(ConfigDiff::values(&old, &old).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn differences_can_be_shown_from_other_trees() {
+        let raw = |token: &str| json!({ "token": token, "tls": { "key": token } });
+        let shown = json!({ "token": "[REDACTED]" });
+
+        let diff = ConfigDiff::values(&raw("old"), &raw("new")).shown_from(&shown, &shown);
+        assert_eq!(
+            diff.to_string(),
+            [
+                "~ /tls/key: \"old\" -> \"new\"",
+                "~ /token: \"[REDACTED]\" -> \"[REDACTED]\"",
+            ]
+            .join("\n")
+        );
+
+        let diff = ConfigDiff::values(&json!({}), &raw("new")).shown_from(&json!({}), &shown);
+        assert_eq!(
+            diff.to_string(),
+            "+ /tls = {\"key\":\"new\"}\n+ /token = \"[REDACTED]\""
+        );
+    }
+}
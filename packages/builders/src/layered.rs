@@ -0,0 +1,908 @@
+//! Layered configuration loading for any [`JsonConfig`](crate::JsonConfig) type
+//!
+//! [`LayeredConfig`] merges several sources into one JSON tree and then
+//! deserializes it. Layers are applied in the order they are added, so
+//! later layers win:
+//!
+//! ```ignore
+//! let loaded = LayeredConfig::<ServiceConfig>::new()
+//!     .defaults(&ServiceConfig::default())
+//!     .optional_file("service.toml")
+//!     .env("APP")                        // APP__SERVER__PORT=8080
+//!     .set("database.pool_size", "32")   // e.g. from --set on the CLI
+//!     .load()?;
+//!
+//! assert_eq!(loaded.source_of("server.port"), Some("env:APP__SERVER__PORT"));
+//! ```
+//!
+//! Objects are merged key by key; anything else, lists included, is
+//! replaced. Values from the environment and overrides are strings and are
+//! converted using the value they replace as a guide: a string stays a
+//! string, a list is split on commas, and a number also accepts sizes like
+//! `64MB` so [`ByteSize`](sugars_collections::ByteSize) fields can be set
+//! from text. JSON files always work; TOML and YAML need the `toml` and
+//! `yaml` features.
+//!
+//! # Profiles
+//!
+//! A file may carry named overlays under a top-level `profile` table, e.g.
+//! `[profile.prod]` in TOML. When a profile is selected, each file's
+//! overlay for it is deep-merged right after that file's base values:
+//!
+//! ```ignore
+//! let config = LayeredConfig::<ServiceConfig>::new()
+//!     .defaults(&ServiceConfig::default())
+//!     .file("service.toml")               // base + [profile.staging], [profile.prod]
+//!     .profile_env("APP_PROFILE")         // or .profile("prod")
+//!     .append("server.allowed_hosts")     // overlays add to this list
+//!     .load()?;
+//!
+//! print!("{}", config.explain());
+//! ```
+//!
+//! Lists in an overlay replace the base list unless their path was passed
+//! to [`append`](LayeredConfig::append). Appending treats a missing value as
+//! an empty list and a single value as a list of one, matching how
+//! [`ZeroOneOrMany`](sugars_collections::ZeroOneOrMany) serializes.
+
+use crate::ConfigBuilder;
+use crate::diff::ConfigDiff;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use sugars_collections::ByteSize;
+
+/// Separator between the prefix and each path segment of an env var name
+const ENV_SEPARATOR: &str = "__";
+
+/// Top-level table of a config file holding its profile overlays
+pub const PROFILE_KEY: &str = "profile";
+
+/// Error produced while loading a [`LayeredConfig`]
+#[derive(Debug)]
+pub enum LayeredConfigError {
+    /// A required file could not be read
+    Io {
+        /// The file that failed
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// A layer could not be parsed or serialized
+    Parse {
+        /// Label of the layer
+        layer: String,
+        /// What went wrong
+        message: String,
+    },
+    /// A file's format is unknown or its feature is not enabled
+    UnsupportedFormat(PathBuf),
+    /// An override was not of the form `path=value`
+    InvalidOverride(String),
+    /// The merged tree does not match the target type
+    Deserialize(serde_json::Error),
+    /// The selected profile has no overlay in any file
+    UnknownProfile {
+        /// The selected profile
+        name: String,
+        /// Profiles the files do define
+        available: Vec<String>,
+    },
+}
+
+impl fmt::Display for LayeredConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "failed to read {}: {source}", path.display()),
+            Self::Parse { layer, message } => write!(f, "invalid config in {layer}: {message}"),
+            Self::UnsupportedFormat(path) => write!(
+                f,
+                "unsupported config format for {} (TOML and YAML need the `toml` and `yaml` features)",
+                path.display()
+            ),
+            Self::InvalidOverride(arg) => {
+                write!(f, "invalid override `{arg}`, expected `path=value`")
+            }
+            Self::Deserialize(error) => write!(f, "merged config is invalid: {error}"),
+            Self::UnknownProfile { name, available } if available.is_empty() => {
+                write!(f, "unknown profile `{name}`: no file defines any profiles")
+            }
+            Self::UnknownProfile { name, available } => write!(
+                f,
+                "unknown profile `{name}`, expected one of: {}",
+                available.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayeredConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Deserialize(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// One source of configuration values
+#[derive(Clone)]
+enum Layer {
+    Value {
+        name: String,
+        value: Result<Value, String>,
+    },
+    File {
+        path: PathBuf,
+        required: bool,
+    },
+    Env {
+        prefix: String,
+        vars: Option<Vec<(String, String)>>,
+    },
+    Overrides(Vec<String>),
+}
+
+/// How the active profile is chosen
+#[derive(Clone)]
+enum Profile {
+    Named(String),
+    Env(String),
+}
+
+/// Builder that merges configuration layers into a `T`
+pub struct LayeredConfig<T> {
+    layers: Vec<Layer>,
+    profile: Option<Profile>,
+    append: BTreeSet<String>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> LayeredConfig<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Start with no layers
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            profile: None,
+            append: BTreeSet::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Add a complete config as the `defaults` layer
+    pub fn defaults(self, defaults: &T) -> Self {
+        self.layer("defaults", defaults)
+    }
+
+    /// Add any serializable value as a named layer
+    ///
+    /// Inline [`Secret`](crate::Secret) values are kept, not redacted.
+    pub fn layer(mut self, name: impl Into<String>, value: &impl Serialize) -> Self {
+        let value = crate::secret::exposed(|| serde_json::to_value(value));
+        self.layers.push(Layer::Value {
+            name: name.into(),
+            value: value.map_err(|error| error.to_string()),
+        });
+        self
+    }
+
+    /// Add a file that must exist; the format follows its extension
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            required: true,
+        });
+        self
+    }
+
+    /// Add a file that is skipped if it does not exist
+    pub fn optional_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            required: false,
+        });
+        self
+    }
+
+    /// Add process environment variables named `{prefix}__SECTION__KEY`
+    ///
+    /// Segments are lowercased, so `APP__DATABASE__POOL_SIZE` sets
+    /// `database.pool_size`. The environment is read when [`load`](Self::load)
+    /// runs.
+    pub fn env(mut self, prefix: impl Into<String>) -> Self {
+        self.layers.push(Layer::Env {
+            prefix: prefix.into(),
+            vars: None,
+        });
+        self
+    }
+
+    /// Like [`env`](Self::env), but reading the given variables instead of
+    /// the process environment
+    pub fn env_vars<K, V>(
+        mut self,
+        prefix: impl Into<String>,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.layers.push(Layer::Env {
+            prefix: prefix.into(),
+            vars: Some(
+                vars.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+        });
+        self
+    }
+
+    /// Override one value by dotted path, e.g. `set("server.port", "8080")`
+    pub fn set(self, path: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.overrides([format!("{}={}", path.as_ref(), value.as_ref())])
+    }
+
+    /// Add `path=value` overrides, typically collected from the command line
+    pub fn overrides<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.layers
+            .push(Layer::Overrides(args.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Apply the overlays of the named profile
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(Profile::Named(name.into()));
+        self
+    }
+
+    /// Apply the overlays of the profile named by an environment variable,
+    /// if it is set and not empty
+    ///
+    /// The variable is read when [`load`](Self::load) runs. This replaces
+    /// any earlier [`profile`](Self::profile) call, and vice versa.
+    pub fn profile_env(mut self, var: impl Into<String>) -> Self {
+        self.profile = Some(Profile::Env(var.into()));
+        self
+    }
+
+    /// Make profile overlays append to the list at a dotted path instead of
+    /// replacing it
+    pub fn append(mut self, path: impl Into<String>) -> Self {
+        self.append.insert(path.into());
+        self
+    }
+
+    /// The profile that [`load`](Self::load) would apply
+    pub fn selected_profile(&self) -> Option<String> {
+        match self.profile.as_ref()? {
+            Profile::Named(name) => Some(name.clone()),
+            Profile::Env(var) => std::env::var(var).ok().filter(|name| !name.is_empty()),
+        }
+    }
+
+    /// What the selected profile changes compared with the base config
+    ///
+    /// Empty when no profile is selected.
+    pub fn profile_diff(&self) -> Result<ConfigDiff, LayeredConfigError> {
+        let Some(name) = self.selected_profile() else {
+            return Ok(ConfigDiff::default());
+        };
+        let base = self.load_with(None)?;
+        let profiled = self.load_with(Some(&name))?;
+        Ok(ConfigDiff::values(&base.value, &profiled.value))
+    }
+
+    /// Merge every layer and deserialize the result
+    ///
+    /// Files and the environment are read on every call, so loading again
+    /// picks up their changes.
+    pub fn load(&self) -> Result<LoadedConfig<T>, LayeredConfigError> {
+        self.load_with(self.selected_profile().as_deref())
+    }
+
+    fn load_with(&self, profile: Option<&str>) -> Result<LoadedConfig<T>, LayeredConfigError> {
+        let mut merged = Merged::default();
+        let mut available = BTreeSet::new();
+        let mut found = false;
+        for layer in self.layers.iter().cloned() {
+            match layer {
+                Layer::Value { name, value } => {
+                    let value = value.map_err(|message| LayeredConfigError::Parse {
+                        layer: name.clone(),
+                        message,
+                    })?;
+                    merged.merge(value, &name);
+                }
+                Layer::File { path, required } => {
+                    let contents = match std::fs::read_to_string(&path) {
+                        Ok(contents) => contents,
+                        Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => {
+                            continue;
+                        }
+                        Err(source) => return Err(LayeredConfigError::Io { path, source }),
+                    };
+                    let mut value = parse_file(&path, &contents)?;
+                    let label = format!("file:{}", path.display());
+                    let mut profiles = take_profiles(&mut value, &label)?;
+                    merged.merge(value, &label);
+                    if let Some(name) = profile
+                        && let Some(overlay) = profiles.remove(name)
+                    {
+                        let label = format!("{label}#{PROFILE_KEY}.{name}");
+                        merged.overlay(overlay, &label, &self.append);
+                        found = true;
+                    }
+                    available.extend(profiles.into_keys());
+                }
+                Layer::Env { prefix, vars } => {
+                    let vars = vars.unwrap_or_else(|| std::env::vars().collect());
+                    let mut vars: Vec<_> = vars
+                        .into_iter()
+                        .filter_map(|(key, value)| {
+                            let path = env_path(&prefix, &key)?;
+                            Some((key, path, value))
+                        })
+                        .collect();
+                    // Apply in a stable order regardless of the environment's
+                    vars.sort();
+                    for (key, path, value) in vars {
+                        merged.set(&path, &value, &format!("env:{key}"));
+                    }
+                }
+                Layer::Overrides(args) => {
+                    for arg in args {
+                        let Some((path, value)) = arg.split_once('=') else {
+                            return Err(LayeredConfigError::InvalidOverride(arg));
+                        };
+                        let path: Vec<String> = path.trim().split('.').map(String::from).collect();
+                        if path.iter().any(String::is_empty) {
+                            return Err(LayeredConfigError::InvalidOverride(arg));
+                        }
+                        merged.set(&path, value, "override");
+                    }
+                }
+            }
+        }
+
+        if let Some(name) = profile
+            && !found
+        {
+            return Err(LayeredConfigError::UnknownProfile {
+                name: name.to_string(),
+                available: available.into_iter().collect(),
+            });
+        }
+
+        let config = serde_json::from_value(merged.value.clone())
+            .map_err(LayeredConfigError::Deserialize)?;
+        Ok(LoadedConfig {
+            config,
+            value: merged.value,
+            sources: merged.sources,
+            profile: profile.map(String::from),
+        })
+    }
+}
+
+impl<T> LayeredConfig<T> {
+    /// Paths of the file layers, in the order they were added
+    #[cfg(feature = "watch")]
+    pub(crate) fn files(&self) -> impl Iterator<Item = &Path> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::File { path, .. } => Some(path.as_path()),
+            _ => None,
+        })
+    }
+}
+
+impl<T> Default for LayeredConfig<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ConfigBuilder<T> for LayeredConfig<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    type Error = LayeredConfigError;
+
+    fn build(self) -> Result<T, Self::Error> {
+        self.load().map(|loaded| loaded.config)
+    }
+
+    fn validate(&self) -> Result<(), Self::Error> {
+        self.load().map(drop)
+    }
+}
+
+/// Result of [`LayeredConfig::load`]
+#[derive(Debug, Clone)]
+pub struct LoadedConfig<T> {
+    /// The merged configuration
+    pub config: T,
+    /// The merged tree the configuration was deserialized from
+    pub value: Value,
+    /// Layer that supplied each leaf value, keyed by dotted path
+    pub sources: BTreeMap<String, String>,
+    /// The profile whose overlays were applied
+    pub profile: Option<String>,
+}
+
+impl<T> LoadedConfig<T> {
+    /// Label of the layer that supplied the value at `path`
+    ///
+    /// Labels are `defaults` (or the name given to [`LayeredConfig::layer`]),
+    /// `file:<path>`, `env:<VAR>` and `override`.
+    pub fn source_of(&self, path: &str) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    /// Every effective value and the layer it came from, one per line
+    ///
+    /// Values are shown as `config` serializes, so inline
+    /// [`Secret`](crate::Secret)s appear as `"[REDACTED]"`.
+    ///
+    /// ```text
+    /// # profile: prod
+    /// server.host = "0.0.0.0"  # file:service.toml#profile.prod
+    /// server.port = 8080       # env:APP__SERVER__PORT
+    /// ```
+    pub fn explain(&self) -> Explain<'_>
+    where
+        T: Serialize,
+    {
+        Explain {
+            value: serde_json::to_value(&self.config).unwrap_or_default(),
+            sources: &self.sources,
+            profile: self.profile.as_deref(),
+        }
+    }
+}
+
+/// Display of a [`LoadedConfig`], created by [`LoadedConfig::explain`]
+#[derive(Debug, Clone)]
+pub struct Explain<'a> {
+    value: Value,
+    sources: &'a BTreeMap<String, String>,
+    profile: Option<&'a str>,
+}
+
+impl fmt::Display for Explain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(profile) = self.profile {
+            writeln!(f, "# profile: {profile}")?;
+        }
+        let lines: Vec<(String, &str)> = self
+            .sources
+            .iter()
+            .map(|(path, source)| {
+                let value = path
+                    .split('.')
+                    .try_fold(&self.value, |value, key| value.get(key))
+                    .unwrap_or(&Value::Null);
+                (format!("{path} = {value}"), source.as_str())
+            })
+            .collect();
+        let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+        for (line, source) in lines {
+            writeln!(f, "{line:<width$}  # {source}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove a file's `profile` table, returning its overlays by name
+fn take_profiles(
+    value: &mut Value,
+    layer: &str,
+) -> Result<BTreeMap<String, Value>, LayeredConfigError> {
+    let Some(profiles) = value
+        .as_object_mut()
+        .and_then(|root| root.remove(PROFILE_KEY))
+    else {
+        return Ok(BTreeMap::new());
+    };
+    let Value::Object(profiles) = profiles else {
+        return Err(LayeredConfigError::Parse {
+            layer: layer.to_string(),
+            message: format!("`{PROFILE_KEY}` must be a table of named profiles"),
+        });
+    };
+    Ok(profiles.into_iter().collect())
+}
+
+/// The tree being built and where each leaf came from
+#[derive(Default)]
+struct Merged {
+    value: Value,
+    sources: BTreeMap<String, String>,
+}
+
+impl Merged {
+    fn merge(&mut self, incoming: Value, layer: &str) {
+        self.overlay(incoming, layer, &BTreeSet::new());
+    }
+
+    /// Merge, appending to the lists at the `append` paths
+    fn overlay(&mut self, incoming: Value, layer: &str, append: &BTreeSet<String>) {
+        let mut merge = Merge {
+            path: Vec::new(),
+            layer,
+            append,
+            sources: &mut self.sources,
+        };
+        merge.merge(&mut self.value, incoming);
+    }
+
+    /// Set one value from text, shaped after the value it replaces
+    fn set(&mut self, path: &[String], raw: &str, layer: &str) {
+        let existing = path
+            .iter()
+            .try_fold(&self.value, |value, key| value.get(key.as_str()));
+        let mut value = coerce(raw, existing);
+        for key in path.iter().rev() {
+            let mut object = Map::new();
+            object.insert(key.clone(), value);
+            value = Value::Object(object);
+        }
+        self.merge(value, layer);
+    }
+}
+
+/// State of one deep merge
+struct Merge<'a> {
+    path: Vec<String>,
+    layer: &'a str,
+    append: &'a BTreeSet<String>,
+    sources: &'a mut BTreeMap<String, String>,
+}
+
+impl Merge<'_> {
+    fn merge(&mut self, target: &mut Value, incoming: Value) {
+        match (target, incoming) {
+            (Value::Object(target), Value::Object(incoming)) => {
+                for (key, value) in incoming {
+                    self.path.push(key.clone());
+                    self.merge(target.entry(key).or_insert(Value::Null), value);
+                    self.path.pop();
+                }
+            }
+            (target, incoming) => {
+                let prefix = self.path.join(".");
+                if self.append.contains(&prefix) && is_list(target) && is_list(&incoming) {
+                    let mut items = into_items(target.take());
+                    items.extend(into_items(incoming));
+                    *target = Value::Array(items);
+                    let layer = match self.sources.remove(&prefix) {
+                        Some(base) => format!("{base} + {}", self.layer),
+                        None => self.layer.to_string(),
+                    };
+                    self.sources.insert(prefix, layer);
+                    return;
+                }
+                self.sources.retain(|key, _| !is_within(key, &prefix));
+                record_leaves(&incoming, &mut self.path, self.layer, self.sources);
+                *target = incoming;
+            }
+        }
+    }
+}
+
+/// Whether a value can be appended to, as a `ZeroOneOrMany` can
+fn is_list(value: &Value) -> bool {
+    !matches!(value, Value::Object(_))
+}
+
+/// The items of a list value; `null` is empty and a scalar is one item
+fn into_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Null => Vec::new(),
+        Value::Array(items) => items,
+        item => vec![item],
+    }
+}
+
+fn record_leaves(
+    value: &Value,
+    path: &mut Vec<String>,
+    layer: &str,
+    sources: &mut BTreeMap<String, String>,
+) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                path.push(key.clone());
+                record_leaves(value, path, layer, sources);
+                path.pop();
+            }
+        }
+        _ => {
+            sources.insert(path.join("."), layer.to_string());
+        }
+    }
+}
+
+fn is_within(key: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || key == prefix
+        || key
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Convert text to JSON, using the value it replaces as a guide
+fn coerce(raw: &str, existing: Option<&Value>) -> Value {
+    let trimmed = raw.trim();
+    match existing {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        Some(Value::Array(items)) => {
+            if trimmed.starts_with('[')
+                && let Ok(value @ Value::Array(_)) = serde_json::from_str(trimmed)
+            {
+                return value;
+            }
+            Value::Array(
+                trimmed
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| coerce(item, items.first()))
+                    .collect(),
+            )
+        }
+        Some(Value::Bool(_)) => trimmed
+            .parse()
+            .map_or_else(|_| Value::String(raw.to_string()), Value::Bool),
+        Some(Value::Number(_)) => number(trimmed)
+            .or_else(|| {
+                trimmed
+                    .parse::<ByteSize>()
+                    .ok()
+                    .map(|size| Value::from(size.as_bytes()))
+            })
+            .unwrap_or_else(|| Value::String(raw.to_string())),
+        _ => infer(raw),
+    }
+}
+
+/// Best guess for text with nothing to guide it
+fn infer(raw: &str) -> Value {
+    let trimmed = raw.trim();
+    if let Ok(flag) = trimmed.parse::<bool>() {
+        return Value::Bool(flag);
+    }
+    if let Some(number) = number(trimmed) {
+        return number;
+    }
+    if (trimmed.starts_with('[') || trimmed.starts_with('{'))
+        && let Ok(value) = serde_json::from_str(trimmed)
+    {
+        return value;
+    }
+    Value::String(raw.to_string())
+}
+
+fn number(text: &str) -> Option<Value> {
+    if let Ok(integer) = text.parse::<i64>() {
+        return Some(Value::from(integer));
+    }
+    if let Ok(integer) = text.parse::<u64>() {
+        return Some(Value::from(integer));
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|float| float.is_finite())
+        .map(Value::from)
+}
+
+/// Map `{prefix}__SECTION__KEY` to `["section", "key"]`
+fn env_path(prefix: &str, key: &str) -> Option<Vec<String>> {
+    let rest = key.strip_prefix(prefix)?.strip_prefix(ENV_SEPARATOR)?;
+    let path: Vec<String> = rest
+        .split(ENV_SEPARATOR)
+        .map(str::to_ascii_lowercase)
+        .collect();
+    (!path.iter().any(String::is_empty)).then_some(path)
+}
+
+fn parse_file(path: &Path, contents: &str) -> Result<Value, LayeredConfigError> {
+    let parse_error = |message: String| LayeredConfigError::Parse {
+        layer: format!("file:{}", path.display()),
+        message,
+    };
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => {
+            serde_json::from_str(contents).map_err(|error| parse_error(error.to_string()))
+        }
+        #[cfg(feature = "toml")]
+        Some("toml") => toml::from_str(contents).map_err(|error| parse_error(error.to_string())),
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(contents).map_err(|error| parse_error(error.to_string()))
+        }
+        _ => Err(LayeredConfigError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AuthConfig;
+    use serde::Deserialize;
+    use sugars_collections::ZeroOneOrMany;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Server {
+        host: String,
+        port: u16,
+        max_body: ByteSize,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Service {
+        name: String,
+        server: Server,
+        peers: ZeroOneOrMany<String>,
+    }
+
+    fn defaults() -> Service {
+        Service {
+            name: "svc".to_string(),
+            server: Server {
+                host: "127.0.0.1".to_string(),
+                port: 80,
+                max_body: ByteSize::kilobytes(64),
+            },
+            peers: ZeroOneOrMany::none(),
+        }
+    }
+
+    #[test]
+    fn later_layers_win_and_sources_are_tracked() {
+        let path = std::env::temp_dir().join(format!("sugars-layered-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"server": {"host": "0.0.0.0", "port": 8080}}"#).unwrap();
+
+        let loaded = LayeredConfig::new()
+            .defaults(&defaults())
+            .file(&path)
+            .env_vars(
+                "APP",
+                [
+                    ("APP__SERVER__PORT", "9090"),
+                    ("APP__PEERS", "a.internal, b.internal"),
+                    ("OTHER__NAME", "ignored"),
+                ],
+            )
+            .set("server.max_body", "10MB")
+            .load()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let config: Service = loaded.config;
+        assert_eq!(config.name, "svc");
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.server.max_body, ByteSize::megabytes(10));
+        assert_eq!(
+            config.peers,
+            ZeroOneOrMany::many(vec!["a.internal".to_string(), "b.internal".to_string()])
+        );
+
+        let file_label = format!("file:{}", path.display());
+        assert_eq!(loaded.sources["name"], "defaults");
+        assert_eq!(loaded.sources["server.host"], file_label);
+        assert_eq!(loaded.sources["server.port"], "env:APP__SERVER__PORT");
+        assert_eq!(loaded.sources["server.max_body"], "override");
+    }
+
+    #[test]
+    fn bad_input_is_reported() {
+        let result = LayeredConfig::new()
+            .defaults(&defaults())
+            .overrides(["server.port"])
+            .load();
+        assert!(matches!(
+            result,
+            Err(LayeredConfigError::InvalidOverride(_))
+        ));
+
+        let result = LayeredConfig::new()
+            .defaults(&defaults())
+            .set("server.port", "eighty")
+            .load();
+        assert!(matches!(result, Err(LayeredConfigError::Deserialize(_))));
+
+        let result = LayeredConfig::<Service>::new().file("missing.json").load();
+        assert!(matches!(result, Err(LayeredConfigError::Io { .. })));
+    }
+
+    #[test]
+    fn inline_secrets_in_value_layers_are_kept() {
+        let loaded = LayeredConfig::<AuthConfig>::new()
+            .defaults(&AuthConfig::jwt("s3cret"))
+            .load()
+            .unwrap();
+        assert_eq!(loaded.config.secret.as_ref().unwrap().expose(), "s3cret");
+
+        let loaded = LayeredConfig::<AuthConfig>::new()
+            .defaults(&AuthConfig::jwt("s3cret"))
+            .set("secret", "hunter2")
+            .load()
+            .unwrap();
+        let explain = loaded.explain().to_string();
+        assert!(explain.lines().any(
+            |line| line.starts_with("secret = \"[REDACTED]\"") && line.ends_with("# override")
+        ));
+        assert!(!explain.contains("hunter2"));
+    }
+
+    #[test]
+    fn profiles_overlay_their_file_and_explain_the_result() {
+        let path = std::env::temp_dir().join(format!("sugars-profile-{}.json", std::process::id()));
+        let file = r#"{
+            "peers": "a.internal",
+            "server": {"host": "0.0.0.0"},
+            "profile": {
+                "prod": {"peers": ["b.internal"], "server": {"port": 443}},
+                "dev": {"name": "svc-dev"}
+            }
+        }"#;
+        std::fs::write(&path, file).unwrap();
+        let layers = || {
+            LayeredConfig::<Service>::new()
+                .defaults(&defaults())
+                .file(&path)
+                .append("peers")
+        };
+
+        let loaded = layers().profile("prod").load().unwrap();
+        assert_eq!(loaded.config.name, "svc");
+        assert_eq!(loaded.config.server.port, 443);
+        assert_eq!(loaded.config.server.host, "0.0.0.0");
+        assert_eq!(
+            loaded.config.peers,
+            ZeroOneOrMany::many(vec!["a.internal".to_string(), "b.internal".to_string()])
+        );
+
+        let file_label = format!("file:{}", path.display());
+        let explain = loaded.explain().to_string();
+        let lines: Vec<&str> = explain.lines().collect();
+        assert_eq!(lines[0], "# profile: prod");
+        let peers = "peers = [\"a.internal\",\"b.internal\"]";
+        assert_eq!(
+            lines[1],
+            format!("{:<1$}  # defaults", "name = \"svc\"", peers.len())
+        );
+        assert_eq!(
+            lines[2],
+            format!("{peers}  # {file_label} + {file_label}#profile.prod")
+        );
+
+        let diff = layers().profile("prod").profile_diff().unwrap().to_string();
+        assert_eq!(
+            diff,
+            "~ /peers: \"a.internal\" -> [\"a.internal\",\"b.internal\"]\n~ /server/port: 80 -> 443"
+        );
+
+        let error = layers().profile("prd").load().unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            error.to_string(),
+            "unknown profile `prd`, expected one of: dev, prod"
+        );
+    }
+}
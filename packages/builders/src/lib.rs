@@ -2,10 +2,37 @@
 //!
 //! This module provides reusable components for creating fluent typestate builders
 //! that leverage all cyrup_sugars features seamlessly.
+pub mod async_support;
+pub mod chunk_chain;
 pub mod chunk_handler;
+pub mod diff;
+pub mod layered;
 pub mod llm;
+#[cfg(feature = "tower")]
+pub mod middleware;
+#[cfg(feature = "watch")]
+pub mod reload;
+pub mod secret;
+pub mod state;
+pub mod validation;
+#[cfg(feature = "tracing")]
+pub use chunk_chain::LogChunks;
+pub use chunk_chain::{
+    AccumulateText, ChunkChain, ChunkMiddleware, ChunkStreamExt, CountTokens, DetectToolCalls,
+    Emit, RetryBadChunks, TextBuffer, TokenCount, ToolCall, ToolCalls,
+};
 pub use chunk_handler::*;
+pub use diff::{Change, ConfigDiff, FieldChange};
+pub use layered::{Explain, LayeredConfig, LayeredConfigError, LoadedConfig};
 pub use llm::*;
+#[cfg(feature = "tower")]
+pub use middleware::{
+    AuthLayerError, Claims, Cors, CorsLayer, JwtAuth, JwtAuthLayer, RateLimit, RateLimitLayer,
+};
+#[cfg(feature = "watch")]
+pub use reload::{ConfigChange, HotConfig, ReloadError};
+pub use secret::{Secret, SecretError};
+pub use validation::{Draft, Validate, ValidationError, ValidationErrors, Validator};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap as StdHashMap;
@@ -14,6 +41,10 @@ use sugars_collections::{OneOrMany, ZeroOneOrMany};
 /// Re-export hashbrown for builder convenience
 pub use hashbrown::HashMap;
 
+/// Re-export schemars so configs can `#[derive(JsonSchema)]`
+#[cfg(feature = "schemars")]
+pub use schemars::{self, JsonSchema};
+
 /// Trait for building configuration objects with validation
 pub trait ConfigBuilder<T> {
     /// The error type returned when building fails.
@@ -29,14 +60,52 @@ pub trait ConfigBuilder<T> {
 /// Trait for JSON serializable configurations
 pub trait JsonConfig: Serialize + for<'de> Deserialize<'de> {
     /// Serialize to pretty JSON string
+    ///
+    /// Inline [`Secret`] values are written as `"[REDACTED]"`, so the result
+    /// is safe to log but does not load back; see
+    /// [`to_json_with_secrets`](Self::to_json_with_secrets).
     fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
     }
 
+    /// Serialize to pretty JSON string, writing inline secrets in plain text
+    ///
+    /// For persisting a config that must load back with
+    /// [`from_json`](Self::from_json); never log the result.
+    fn to_json_with_secrets(&self) -> Result<String, serde_json::Error> {
+        secret::exposed(|| serde_json::to_string_pretty(self))
+    }
+
     /// Deserialize from JSON string
     fn from_json(json: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(json)
     }
+
+    /// JSON Schema describing this config, e.g. for editor autocomplete
+    #[cfg(feature = "schemars")]
+    fn schema() -> schemars::Schema
+    where
+        Self: schemars::JsonSchema + Sized,
+    {
+        schemars::schema_for!(Self)
+    }
+
+    /// Self-contained schema for LLM tool parameter definitions
+    ///
+    /// Every subschema is inlined and the `$schema` keyword dropped, since
+    /// tool APIs accept a bare object schema.
+    #[cfg(feature = "schemars")]
+    fn tool_schema() -> serde_json::Value
+    where
+        Self: schemars::JsonSchema + Sized,
+    {
+        let mut schema = schemars::generate::SchemaSettings::default()
+            .with(|settings| settings.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Self>();
+        schema.remove("$schema");
+        schema.to_value()
+    }
 }
 
 /// Automatic implementation for all Serialize + Deserialize types
@@ -77,40 +146,16 @@ where
     }
 }
 
-/// Builder state management
-pub mod state {
-    use std::marker::PhantomData;
-
-    /// Marker trait for builder states
-    pub trait BuilderState {}
-
-    /// Builder is incomplete and missing required fields
-    pub struct Incomplete;
-    impl BuilderState for Incomplete {}
-
-    /// Builder has all required fields
-    pub struct Complete;
-    impl BuilderState for Complete {}
-
-    /// Builder with custom validation state
-    pub struct Validated<T>(PhantomData<T>);
-    impl<T> BuilderState for Validated<T> {}
-
-    /// State transition helpers
-    pub trait StateTransition<To: BuilderState> {
-        /// The output type after transition.
-        type Output;
-        /// Performs the state transition.
-        fn transition(self) -> Self::Output;
-    }
-}
-
 /// Common configuration patterns
 pub mod patterns {
     use super::*;
 
     /// Authentication configuration pattern
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    ///
+    /// Configs written before `secret` was a field kept it in
+    /// `settings.secret`; it is moved to `secret` when deserializing.
+    #[derive(Serialize, Debug, Clone)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
     pub struct AuthConfig {
         /// Authentication methods (e.g., "jwt", "oauth").
         pub methods: OneOrMany<String>,
@@ -118,20 +163,68 @@ pub mod patterns {
         pub token_ttl: u64,
         /// Authentication providers (e.g., "google", "github").
         pub providers: ZeroOneOrMany<String>,
+        /// Signing secret for the jwt method, unless oauth providers issue
+        /// the tokens.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub secret: Option<Secret<String>>,
         /// Additional provider-specific settings.
+        #[cfg_attr(
+            feature = "schemars",
+            schemars(with = "StdHashMap<String, serde_json::Value>")
+        )]
         pub settings: HashMap<String, serde_json::Value>,
     }
 
+    /// [`AuthConfig`] as written, before `settings.secret` is migrated
+    #[derive(Deserialize)]
+    struct AuthConfigFields {
+        methods: OneOrMany<String>,
+        token_ttl: u64,
+        providers: ZeroOneOrMany<String>,
+        #[serde(default)]
+        secret: Option<Secret<String>>,
+        settings: HashMap<String, serde_json::Value>,
+    }
+
+    impl<'de> Deserialize<'de> for AuthConfig {
+        fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+            let mut fields = AuthConfigFields::deserialize(de)?;
+            let secret = match (fields.secret, fields.settings.remove("secret")) {
+                (secret, None) => secret,
+                (None, Some(serde_json::Value::String(spec))) => {
+                    Some(Secret::load(&spec).map_err(serde::de::Error::custom)?)
+                }
+                (None, Some(_)) => {
+                    return Err(serde::de::Error::custom(
+                        "`settings.secret` must be a string",
+                    ));
+                }
+                (Some(_), Some(_)) => {
+                    return Err(serde::de::Error::custom(
+                        "`secret` and `settings.secret` are both set; keep `secret`",
+                    ));
+                }
+            };
+            Ok(Self {
+                methods: fields.methods,
+                token_ttl: fields.token_ttl,
+                providers: fields.providers,
+                secret,
+                settings: fields.settings,
+            })
+        }
+    }
+
     impl AuthConfig {
         /// Creates a JWT authentication configuration.
-        pub fn jwt(secret: &str) -> Self {
+        pub fn jwt(secret: impl Into<Secret<String>>) -> Self {
             Self {
                 methods: OneOrMany::one("jwt".to_string()),
                 token_ttl: 3600,
                 providers: ZeroOneOrMany::none(),
+                secret: Some(secret.into()),
                 settings: {
                     let mut settings = HashMap::new();
-                    settings.insert("secret".to_string(), secret.into());
                     settings.insert("algorithm".to_string(), "HS256".into());
                     settings
                 },
@@ -145,6 +238,7 @@ pub mod patterns {
                     .unwrap_or_else(|_| OneOrMany::one("oauth".to_string())),
                 token_ttl: 7200,
                 providers: providers.into(),
+                secret: None,
                 settings: HashMap::new(),
             }
         }
@@ -152,6 +246,7 @@ pub mod patterns {
 
     /// Rate limiting configuration pattern
     #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
     pub struct RateLimitConfig {
         /// Maximum requests per minute.
         pub requests_per_minute: u32,
@@ -160,6 +255,7 @@ pub mod patterns {
         /// Paths to exclude from rate limiting.
         pub exclude_paths: ZeroOneOrMany<String>,
         /// Custom rate limit rules per path.
+        #[cfg_attr(feature = "schemars", schemars(with = "StdHashMap<String, u32>"))]
         pub custom_rules: HashMap<String, u32>,
     }
 
@@ -193,6 +289,7 @@ pub mod patterns {
 
     /// CORS configuration pattern
     #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
     pub struct CorsConfig {
         /// Allowed origins for CORS requests.
         pub allowed_origins: ZeroOneOrMany<String>,
@@ -243,31 +340,6 @@ pub mod patterns {
     }
 }
 
-/// Async builder support
-pub mod async_support {
-    use sugars_async_task::{AsyncTask, NotResult};
-
-    /// Trait for builders that can deploy/execute asynchronously
-    pub trait AsyncBuilder<T>
-    where
-        T: NotResult,
-    {
-        /// The error type returned when async building fails.
-        type Error;
-
-        /// Execute the builder asynchronously
-        fn execute(self) -> AsyncTask<T>;
-
-        /// Execute with validation
-        fn execute_validated(self) -> AsyncTask<T>
-        where
-            Self: Sized,
-        {
-            self.execute()
-        }
-    }
-}
-
 /// Macro helpers for object literal syntax
 pub mod macros {
     /// Create HashMap with object literal syntax
@@ -341,3 +413,27 @@ pub use patterns::*;
 pub use state::*;
 
 pub use async_support::*;
+
+#[cfg(all(test, feature = "schemars"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn pattern_schemas_understand_collection_types() {
+        let schema = CorsConfig::tool_schema();
+        assert!(schema.get("$schema").is_none());
+        let properties = &schema["properties"];
+        assert_eq!(properties["allowed_methods"]["anyOf"][1]["minItems"], 1);
+        assert_eq!(
+            properties["allowed_origins"]["anyOf"][0],
+            json!({ "type": "null" })
+        );
+
+        let schema = RateLimitConfig::schema();
+        assert_eq!(
+            schema.get("properties").unwrap()["custom_rules"]["type"],
+            "object"
+        );
+    }
+}
@@ -3,11 +3,15 @@
 //! This module implements the exact syntax shown in the README.md file,
 //! demonstrating clean array tuple configuration without exposing macros.
 
+use crate::async_support::{AsyncBuilder, BuildError, BuildFuture};
+use crate::secret::Secret;
+use crate::validation::{Validate, Validator, custom, non_empty, url};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap as StdHashMap;
 
 /// Client builder that supports array tuple syntax
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Client {
     headers: StdHashMap<String, String>,
     options: StdHashMap<String, String>,
@@ -57,12 +61,13 @@ impl Default for Client {
 
 /// Database connection configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Database {
     pub host: String,
     pub port: String,
     pub database: String,
     pub user: String,
-    pub password: Option<String>,
+    pub password: Option<Secret<String>>,
 }
 
 impl Database {
@@ -78,16 +83,55 @@ impl Database {
             port: config.get("port").unwrap_or(&"5432").to_string(),
             database: config.get("database").unwrap_or(&"postgres").to_string(),
             user: config.get("user").unwrap_or(&"postgres").to_string(),
-            password: config.get("password").map(|s| s.to_string()),
+            password: config.get("password").map(|s| Secret::from(*s)),
         }
     }
 }
 
+impl Validate for Database {
+    fn validate(&self, v: &mut Validator) {
+        v.field("host", self.host.as_str(), [non_empty()])
+            .field(
+                "port",
+                self.port.as_str(),
+                [custom("port", |port: &str| {
+                    port.parse::<u16>()
+                        .map(drop)
+                        .map_err(|_| format!("must be a port number, got `{port}`"))
+                })],
+            )
+            .field("database", self.database.as_str(), [non_empty()])
+            .field("user", self.user.as_str(), [non_empty()]);
+    }
+}
+
+/// Checks the fields, then that the host resolves; a failed lookup is
+/// transient, so a retry policy will try it again
+impl AsyncBuilder<Database> for Database {
+    fn attempt(&self) -> BuildFuture<'_, Database> {
+        Box::pin(std::future::ready(Ok(self.clone())))
+    }
+
+    fn validate(&self) -> BuildFuture<'_, ()> {
+        Box::pin(async move {
+            self.validation()?;
+            let port = self.port.parse::<u16>().unwrap_or_default();
+            tokio::net::lookup_host((self.host.as_str(), port))
+                .await
+                .map(drop)
+                .map_err(|error| {
+                    BuildError::transient(format!("cannot resolve `{}`: {error}", self.host))
+                })
+        })
+    }
+}
+
 /// API client builder with fluent interface
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ApiClient {
     endpoint: Option<String>,
-    auth: Option<StdHashMap<String, String>>,
+    auth: Option<StdHashMap<String, Secret<String>>>,
     rate_limit: Option<StdHashMap<String, String>>,
 }
 
@@ -116,7 +160,7 @@ impl ApiClient {
         let auth_map = f();
         let mut auth = StdHashMap::new();
         for (k, v) in auth_map {
-            auth.insert(k.to_string(), v.to_string());
+            auth.insert(k.to_string(), Secret::from(v));
         }
         self.auth = Some(auth);
         self
@@ -154,10 +198,39 @@ impl Default for ApiClient {
     }
 }
 
+impl Validate for ApiClient {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(endpoint) = &self.endpoint {
+            v.field("endpoint", endpoint.as_str(), [url()]);
+        }
+    }
+}
+
+impl AsyncBuilder<BuiltApiClient> for ApiClient {
+    fn attempt(&self) -> BuildFuture<'_, BuiltApiClient> {
+        let built = match &self.endpoint {
+            Some(endpoint) => Ok(BuiltApiClient {
+                endpoint: endpoint.clone(),
+                auth: self.auth.clone().unwrap_or_default(),
+                rate_limit: self.rate_limit.clone().unwrap_or_default(),
+            }),
+            None => Err(BuildError::MissingField("endpoint")),
+        };
+        Box::pin(std::future::ready(built))
+    }
+
+    fn validate(&self) -> BuildFuture<'_, ()> {
+        Box::pin(std::future::ready(
+            self.validation().map_err(BuildError::from),
+        ))
+    }
+}
+
 /// A built and configured API client
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct BuiltApiClient {
     pub endpoint: String,
-    pub auth: StdHashMap<String, String>,
+    pub auth: StdHashMap<String, Secret<String>>,
     pub rate_limit: StdHashMap<String, String>,
 }
@@ -0,0 +1,646 @@
+//! Tower layers that enforce the [`patterns`](crate::patterns) configs
+//!
+//! Each config turns into a [`Layer`] for `http` services:
+//!
+//! ```ignore
+//! let service = ServiceBuilder::new()
+//!     .layer(CorsConfig::permissive().layer())
+//!     .layer(RateLimitConfig::simple(600).layer())
+//!     .layer(AuthConfig::jwt(&secret).layer()?)
+//!     .service(app);
+//! ```
+//!
+//! Rejections are answered by the layer itself with an empty
+//! `ResBody::default()` body, so the wrapped service never sees them.
+
+use crate::patterns::{AuthConfig, CorsConfig, RateLimitConfig};
+use http::header::{self, HeaderMap, HeaderValue};
+use http::{Method, Request, Response, StatusCode};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use sugars_async_task::RateLimiter;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Future returned by the middleware services
+pub type ResponseFuture<B, E> = Pin<Box<dyn Future<Output = Result<Response<B>, E>> + Send>>;
+
+/// Returns true if `path` is `prefix` or lies below it
+fn under(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'),
+        None => false,
+    }
+}
+
+fn respond<B: Default>(status: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = status;
+    response
+}
+
+/// Limit applied to one route; `None` rejects every request
+type Bucket = Option<RateLimiter>;
+
+struct Buckets {
+    default: Bucket,
+    /// Custom rules, longest prefix first
+    rules: Vec<(String, Bucket)>,
+    exclude: Vec<String>,
+}
+
+impl Buckets {
+    fn for_path(&self, path: &str) -> Option<&Bucket> {
+        if self.exclude.iter().any(|prefix| under(path, prefix)) {
+            return None;
+        }
+        let rule = self.rules.iter().find(|(prefix, _)| under(path, prefix));
+        Some(rule.map_or(&self.default, |(_, bucket)| bucket))
+    }
+}
+
+/// Token-bucket rate limiting from a [`RateLimitConfig`]
+///
+/// Requests under an `exclude_paths` entry are never limited. A request
+/// under a `custom_rules` path draws from that rule's bucket (the longest
+/// matching path wins); everything else shares the default bucket. Custom
+/// rules keep the default's burst-to-rate ratio, and a limit of 0 rejects
+/// every request. Rejected requests get `429 Too Many Requests` with a
+/// `Retry-After` header.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    buckets: Arc<Buckets>,
+}
+
+impl RateLimitConfig {
+    /// Enforce this config as a tower layer
+    pub fn layer(&self) -> RateLimitLayer {
+        let bucket = |limit: u32| {
+            (limit > 0).then(|| {
+                let burst = u64::from(limit) * u64::from(self.burst_size)
+                    / u64::from(self.requests_per_minute.max(1));
+                let burst = u32::try_from(burst).unwrap_or(u32::MAX).max(1);
+                RateLimiter::per_minute(limit).with_burst(burst)
+            })
+        };
+        let mut rules: Vec<_> = self
+            .custom_rules
+            .iter()
+            .map(|(path, limit)| (path.clone(), bucket(*limit)))
+            .collect();
+        rules.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        RateLimitLayer {
+            buckets: Arc::new(Buckets {
+                default: bucket(self.requests_per_minute),
+                rules,
+                exclude: self.exclude_paths.iter().cloned().collect(),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> RateLimit<S> {
+        RateLimit {
+            inner,
+            buckets: Arc::clone(&self.buckets),
+        }
+    }
+}
+
+impl fmt::Debug for RateLimitLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitLayer")
+            .field("rules", &self.buckets.rules.len())
+            .field("exclude", &self.buckets.exclude)
+            .finish()
+    }
+}
+
+/// Service produced by [`RateLimitLayer`]
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    buckets: Arc<Buckets>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RateLimit<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<ResBody, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let rejected = match self.buckets.for_path(request.uri().path()) {
+            None => None,
+            Some(Some(limiter)) if limiter.try_acquire() => None,
+            Some(bucket) => Some(bucket.as_ref().map(RateLimiter::interval)),
+        };
+        let Some(retry_after) = rejected else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        let mut response = respond(StatusCode::TOO_MANY_REQUESTS);
+        if let Some(interval) = retry_after {
+            let seconds = interval.as_secs() + u64::from(interval.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        Box::pin(std::future::ready(Ok(response)))
+    }
+}
+
+/// CORS handling from a [`CorsConfig`]
+///
+/// Preflight requests are answered directly: `204 No Content` with the
+/// allowed methods, headers and max age, or `403 Forbidden` if the origin,
+/// method or headers are not allowed. Other requests from an allowed
+/// origin pass through and get `Access-Control-Allow-Origin` added to the
+/// response. An origin of `"*"` allows any origin; with `credentials` the
+/// request's origin is echoed back instead, as browsers require.
+#[derive(Clone)]
+pub struct CorsLayer {
+    policy: Arc<CorsPolicy>,
+}
+
+struct CorsPolicy {
+    any_origin: bool,
+    origins: Vec<String>,
+    methods: Vec<String>,
+    any_header: bool,
+    headers: Vec<String>,
+    max_age: u64,
+    credentials: bool,
+}
+
+impl CorsConfig {
+    /// Enforce this config as a tower layer
+    pub fn layer(&self) -> CorsLayer {
+        CorsLayer {
+            policy: Arc::new(CorsPolicy {
+                any_origin: self.allowed_origins.iter().any(|origin| origin == "*"),
+                origins: self.allowed_origins.iter().cloned().collect(),
+                methods: self
+                    .allowed_methods
+                    .iter()
+                    .map(|method| method.to_ascii_uppercase())
+                    .collect(),
+                any_header: self.allowed_headers.iter().any(|name| name == "*"),
+                headers: self
+                    .allowed_headers
+                    .iter()
+                    .map(|name| name.to_ascii_lowercase())
+                    .collect(),
+                max_age: self.max_age,
+                credentials: self.credentials,
+            }),
+        }
+    }
+}
+
+impl CorsPolicy {
+    fn allows_origin(&self, origin: &HeaderValue) -> bool {
+        self.any_origin
+            || self
+                .origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin)
+    }
+
+    /// Add the headers every CORS response from an allowed origin carries
+    fn allow_origin(&self, origin: HeaderValue, headers: &mut HeaderMap) {
+        if self.any_origin && !self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+        } else {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        if self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight<B: Default>(&self, request: &HeaderMap) -> Response<B> {
+        let origin = request
+            .get(header::ORIGIN)
+            .filter(|origin| self.allows_origin(origin));
+        let method = request
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|method| method.to_str().ok())
+            .filter(|method| self.methods.iter().any(|allowed| allowed == method));
+        let requested = request
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|names| names.to_str().ok())
+            .unwrap_or("");
+        let headers_allowed = self.any_header
+            || requested
+                .split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty())
+                .all(|name| self.headers.contains(&name));
+
+        let (Some(origin), Some(_), true) = (origin, method, headers_allowed) else {
+            return respond(StatusCode::FORBIDDEN);
+        };
+        let mut response = respond(StatusCode::NO_CONTENT);
+        let headers = response.headers_mut();
+        self.allow_origin(origin.clone(), headers);
+        if let Ok(methods) = HeaderValue::from_str(&self.methods.join(", ")) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        let allowed_headers = if self.any_header {
+            // `*` is taken literally when credentials are allowed
+            HeaderValue::from_str(if self.credentials { requested } else { "*" })
+        } else {
+            HeaderValue::from_str(&self.headers.join(", "))
+        };
+        if let Ok(allowed_headers) = allowed_headers
+            && !allowed_headers.is_empty()
+        {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        headers.insert(
+            header::ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from(self.max_age),
+        );
+        response
+    }
+}
+
+impl<S> Layer<S> for CorsLayer {
+    type Service = Cors<S>;
+
+    fn layer(&self, inner: S) -> Cors<S> {
+        Cors {
+            inner,
+            policy: Arc::clone(&self.policy),
+        }
+    }
+}
+
+impl fmt::Debug for CorsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CorsLayer")
+            .field("origins", &self.policy.origins)
+            .field("methods", &self.policy.methods)
+            .finish()
+    }
+}
+
+/// Service produced by [`CorsLayer`]
+#[derive(Clone)]
+pub struct Cors<S> {
+    inner: S,
+    policy: Arc<CorsPolicy>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Cors<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<ResBody, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let headers = request.headers();
+        if request.method() == Method::OPTIONS
+            && headers.contains_key(header::ORIGIN)
+            && headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            return Box::pin(std::future::ready(Ok(self.policy.preflight(headers))));
+        }
+
+        let origin = headers
+            .get(header::ORIGIN)
+            .filter(|origin| self.policy.allows_origin(origin))
+            .cloned();
+        let policy = Arc::clone(&self.policy);
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = response.await?;
+            if let Some(origin) = origin {
+                policy.allow_origin(origin, response.headers_mut());
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Why an [`AuthConfig`] could not be turned into a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthLayerError {
+    /// `methods` does not include `"jwt"`
+    NotJwt,
+    /// `secret` is not set
+    MissingSecret,
+    /// `settings.algorithm` is not one of HS256, HS384 or HS512
+    UnsupportedAlgorithm(String),
+}
+
+impl fmt::Display for AuthLayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthLayerError::NotJwt => write!(f, "auth config does not enable the jwt method"),
+            AuthLayerError::MissingSecret => write!(f, "jwt auth config has no `secret`"),
+            AuthLayerError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported jwt algorithm `{algorithm}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthLayerError {}
+
+/// Claims of a verified token, added to the request's extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims(pub serde_json::Value);
+
+/// Bearer JWT verification from [`AuthConfig::jwt`]
+///
+/// Requests without a valid, unexpired `Authorization: Bearer` token get
+/// `401 Unauthorized` with a `WWW-Authenticate` challenge. Verified
+/// requests reach the inner service with their [`Claims`] attached.
+#[derive(Clone)]
+pub struct JwtAuthLayer {
+    verifier: Arc<Verifier>,
+}
+
+struct Verifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl AuthConfig {
+    /// Enforce this config's JWT method as a tower layer
+    pub fn layer(&self) -> Result<JwtAuthLayer, AuthLayerError> {
+        if !self.methods.iter().any(|method| method == "jwt") {
+            return Err(AuthLayerError::NotJwt);
+        }
+        let secret = self.secret.as_ref().ok_or(AuthLayerError::MissingSecret)?;
+        let name = self
+            .settings
+            .get("algorithm")
+            .and_then(|algorithm| algorithm.as_str())
+            .unwrap_or("HS256");
+        let algorithm = match Algorithm::from_str(name) {
+            Ok(algorithm @ (Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)) => algorithm,
+            _ => return Err(AuthLayerError::UnsupportedAlgorithm(name.to_string())),
+        };
+        Ok(JwtAuthLayer {
+            verifier: Arc::new(Verifier {
+                key: DecodingKey::from_secret(secret.expose().as_bytes()),
+                validation: Validation::new(algorithm),
+            }),
+        })
+    }
+}
+
+impl Verifier {
+    /// Verify the bearer token, or say which challenge to send back
+    fn verify(&self, headers: &HeaderMap) -> Result<Claims, &'static str> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            // Auth schemes are case-insensitive (RFC 7235)
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, token)| token)
+            .ok_or("Bearer")?;
+        jsonwebtoken::decode::<serde_json::Value>(token.trim(), &self.key, &self.validation)
+            .map(|data| Claims(data.claims))
+            .map_err(|_| "Bearer error=\"invalid_token\"")
+    }
+}
+
+impl<S> Layer<S> for JwtAuthLayer {
+    type Service = JwtAuth<S>;
+
+    fn layer(&self, inner: S) -> JwtAuth<S> {
+        JwtAuth {
+            inner,
+            verifier: Arc::clone(&self.verifier),
+        }
+    }
+}
+
+impl fmt::Debug for JwtAuthLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuthLayer")
+            .field("algorithms", &self.verifier.validation.algorithms)
+            .finish()
+    }
+}
+
+/// Service produced by [`JwtAuthLayer`]
+#[derive(Clone)]
+pub struct JwtAuth<S> {
+    inner: S,
+    verifier: Arc<Verifier>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for JwtAuth<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<ResBody, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        match self.verifier.verify(request.headers()) {
+            Ok(claims) => {
+                request.extensions_mut().insert(claims);
+                Box::pin(self.inner.call(request))
+            }
+            Err(challenge) => {
+                let mut response = respond(StatusCode::UNAUTHORIZED);
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(challenge),
+                );
+                Box::pin(std::future::ready(Ok(response)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use tower::{ServiceExt, service_fn};
+
+    /// Inner service that echoes the claims it was given, if any
+    fn app()
+    -> impl Service<
+        Request<()>,
+        Response = Response<String>,
+        Error = Infallible,
+        Future: Send + 'static,
+    > + Clone {
+        service_fn(|request: Request<()>| async move {
+            let body = request
+                .extensions()
+                .get::<Claims>()
+                .map_or_else(String::new, |claims| claims.0["sub"].to_string());
+            Ok::<_, Infallible>(Response::new(body))
+        })
+    }
+
+    fn get(path: &str) -> Request<()> {
+        Request::get(path).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rate_limit_honors_rules_and_exclusions() {
+        let mut config = RateLimitConfig::simple(60);
+        config.burst_size = 2;
+        config.custom_rules.insert("/search".to_string(), 30);
+        let service = config.layer().layer(app());
+
+        for _ in 0..2 {
+            let response = service.clone().oneshot(get("/items")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = service.clone().oneshot(get("/items/7")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        let response = service.clone().oneshot(get("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = service.clone().oneshot(get("/search")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = service.clone().oneshot(get("/search?q=a")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn cors_answers_preflight_and_tags_responses() {
+        let config = CorsConfig::strict(
+            "https://app.example".to_string(),
+            sugars_collections::OneOrMany::one("PUT".to_string()),
+            "content-type".to_string(),
+        );
+        let service = config.layer().layer(app());
+        let preflight = |origin: &str| {
+            Request::options("/items")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "Content-Type")
+                .body(())
+                .unwrap()
+        };
+
+        let response = service
+            .clone()
+            .oneshot(preflight("https://app.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "PUT");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let response = service
+            .clone()
+            .oneshot(preflight("https://evil.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = Request::get("/items")
+            .header(header::ORIGIN, "https://app.example")
+            .body(())
+            .unwrap();
+        let response = service.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::VARY], "origin");
+    }
+
+    #[tokio::test]
+    async fn jwt_layer_verifies_bearer_tokens() {
+        let service = AuthConfig::jwt("s3cret").layer().unwrap().layer(app());
+        let token = |secret: &str| {
+            let claims = serde_json::json!({ "sub": "ada", "exp": u32::MAX });
+            let key = jsonwebtoken::EncodingKey::from_secret(secret.as_bytes());
+            jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &key).unwrap()
+        };
+        let with_token = |token: String| {
+            Request::get("/me")
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(())
+                .unwrap()
+        };
+
+        let response = service
+            .clone()
+            .oneshot(with_token(token("s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "\"ada\"");
+
+        let lowercase = Request::get("/me")
+            .header(header::AUTHORIZATION, format!("bearer {}", token("s3cret")))
+            .body(())
+            .unwrap();
+        let response = service.clone().oneshot(lowercase).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service
+            .clone()
+            .oneshot(with_token(token("guess")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = service.oneshot(get("/me")).await.unwrap();
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+
+        assert_eq!(
+            AuthConfig::oauth("github".to_string()).layer().unwrap_err(),
+            AuthLayerError::MissingSecret
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Hot reload for [`LayeredConfig`]
+//!
+//! [`LayeredConfig::watch`] loads the config once and then watches every
+//! file layer. When one changes, all layers are loaded again, validated if
+//! asked, and compared with the current config:
+//!
+//! ```ignore
+//! let config = LayeredConfig::<ServiceConfig>::new()
+//!     .defaults(&ServiceConfig::default())
+//!     .file("service.json")
+//!     .watch_validated()?;
+//!
+//! let mut changes = config.changes();
+//! while let Some(change) = changes.next().await {
+//!     match change {
+//!         ConfigChange::Updated { config, diff } => apply(&config, &diff),
+//!         ConfigChange::Rejected { error } => warn!("kept last good config: {error}"),
+//!     }
+//! }
+//! ```
+//!
+//! A reload that fails to load or validate is reported as
+//! [`ConfigChange::Rejected`] and the last good config stays current. A
+//! reload that changes nothing is not reported at all. Bursts of file
+//! events, such as an editor's truncate-then-write, are coalesced into one
+//! reload.
+
+use crate::diff::ConfigDiff;
+use crate::layered::{LayeredConfig, LayeredConfigError};
+use crate::validation::{Validate, ValidationErrors};
+use notify::event::{AccessKind, AccessMode, EventKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak, mpsc};
+use std::time::Duration;
+use sugars_async_stream::{AsyncStream, StreamBackend, StreamSender};
+use sugars_async_task::NotResult;
+
+/// Quiet period after a file event before reloading
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Why a reload was rejected
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The layers could not be loaded
+    Load(LayeredConfigError),
+    /// The loaded config failed validation
+    Invalid(ValidationErrors),
+    /// The files could not be watched
+    Watch(notify::Error),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Load(error) => error.fmt(f),
+            ReloadError::Invalid(errors) => write!(f, "config failed validation:\n{errors}"),
+            ReloadError::Watch(error) => write!(f, "cannot watch config files: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReloadError::Load(error) => Some(error),
+            ReloadError::Invalid(errors) => Some(errors),
+            ReloadError::Watch(error) => Some(error),
+        }
+    }
+}
+
+impl From<LayeredConfigError> for ReloadError {
+    fn from(error: LayeredConfigError) -> Self {
+        ReloadError::Load(error)
+    }
+}
+
+/// Outcome of a reload that found something to report
+pub enum ConfigChange<T> {
+    /// A new config was loaded and is now current
+    Updated {
+        /// The new config
+        config: Arc<T>,
+        /// What changed from the previous config, with values as the
+        /// configs serialize so inline secrets read `"[REDACTED]"`
+        diff: ConfigDiff,
+    },
+    /// The reload failed; the last good config is still current
+    Rejected {
+        /// Why it failed
+        error: Arc<ReloadError>,
+    },
+}
+
+impl<T> ConfigChange<T> {
+    /// The new config, if the reload succeeded
+    pub fn config(&self) -> Option<&Arc<T>> {
+        match self {
+            ConfigChange::Updated { config, .. } => Some(config),
+            ConfigChange::Rejected { .. } => None,
+        }
+    }
+
+    /// The error, if the reload was rejected
+    pub fn error(&self) -> Option<&ReloadError> {
+        match self {
+            ConfigChange::Updated { .. } => None,
+            ConfigChange::Rejected { error } => Some(error),
+        }
+    }
+}
+
+impl<T> Clone for ConfigChange<T> {
+    fn clone(&self) -> Self {
+        match self {
+            ConfigChange::Updated { config, diff } => ConfigChange::Updated {
+                config: Arc::clone(config),
+                diff: diff.clone(),
+            },
+            ConfigChange::Rejected { error } => ConfigChange::Rejected {
+                error: Arc::clone(error),
+            },
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ConfigChange<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigChange::Updated { config, diff } => f
+                .debug_struct("Updated")
+                .field("config", config)
+                .field("diff", diff)
+                .finish(),
+            ConfigChange::Rejected { error } => {
+                f.debug_struct("Rejected").field("error", error).finish()
+            }
+        }
+    }
+}
+
+impl<T> NotResult for ConfigChange<T> {}
+
+type Check<T> = fn(&T) -> Result<(), ValidationErrors>;
+type Subscriber<T> = <AsyncStream<ConfigChange<T>> as StreamBackend<ConfigChange<T>>>::Sender;
+
+struct State<T: Send + Sync + 'static> {
+    layers: LayeredConfig<T>,
+    check: Option<Check<T>>,
+    current: Mutex<(Arc<T>, Value)>,
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+    /// Held for the whole of a reload so reloads never interleave
+    reloading: Mutex<()>,
+}
+
+fn lock<V>(mutex: &Mutex<V>) -> MutexGuard<'_, V> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl<T> State<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn reload(&self) -> Option<ConfigChange<T>> {
+        let _reloading = lock(&self.reloading);
+        let change = match load(&self.layers, self.check) {
+            Ok((config, value)) => {
+                let mut current = lock(&self.current);
+                let diff = ConfigDiff::values(&current.1, &value);
+                if diff.is_empty() {
+                    return None;
+                }
+                let shown = |config: &T| serde_json::to_value(config).unwrap_or_default();
+                let diff = diff.shown_from(&shown(&current.0), &shown(&config));
+                let config = Arc::new(config);
+                *current = (Arc::clone(&config), value);
+                ConfigChange::Updated { config, diff }
+            }
+            Err(error) => ConfigChange::Rejected {
+                error: Arc::new(error),
+            },
+        };
+        lock(&self.subscribers).retain(|subscriber| subscriber.send_item(change.clone()).is_ok());
+        Some(change)
+    }
+}
+
+/// A config that reloads itself when its files change
+///
+/// Dropping it stops watching and ends every [`changes`](Self::changes)
+/// stream.
+pub struct HotConfig<T: Send + Sync + 'static> {
+    state: Arc<State<T>>,
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl<T> HotConfig<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// The last config that loaded and validated
+    pub fn current(&self) -> Arc<T> {
+        Arc::clone(&lock(&self.state.current).0)
+    }
+
+    /// Stream of reload outcomes from now on
+    pub fn changes(&self) -> AsyncStream<ConfigChange<T>> {
+        let (sender, stream) = AsyncStream::channel();
+        lock(&self.state.subscribers).push(sender);
+        stream
+    }
+
+    /// Reload now, e.g. on SIGHUP, returning what was also sent to
+    /// subscribers; `None` if nothing changed
+    pub fn reload(&self) -> Option<ConfigChange<T>> {
+        self.state.reload()
+    }
+}
+
+impl<T: Send + Sync + 'static> fmt::Debug for HotConfig<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HotConfig")
+            .field("validated", &self.state.check.is_some())
+            .field("subscribers", &lock(&self.state.subscribers).len())
+            .finish()
+    }
+}
+
+impl<T> LayeredConfig<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Load now and reload whenever a file layer changes
+    pub fn watch(self) -> Result<HotConfig<T>, ReloadError> {
+        watch(self, None)
+    }
+
+    /// Like [`watch`](Self::watch), but a config must also pass
+    /// [`Validate`] to become current, the first one included
+    pub fn watch_validated(self) -> Result<HotConfig<T>, ReloadError>
+    where
+        T: Validate,
+    {
+        watch(self, Some(|config: &T| config.validation()))
+    }
+}
+
+fn load<T>(layers: &LayeredConfig<T>, check: Option<Check<T>>) -> Result<(T, Value), ReloadError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let loaded = layers.load()?;
+    if let Some(check) = check {
+        check(&loaded.config).map_err(ReloadError::Invalid)?;
+    }
+    Ok((loaded.config, loaded.value))
+}
+
+fn watch<T>(layers: LayeredConfig<T>, check: Option<Check<T>>) -> Result<HotConfig<T>, ReloadError>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    // Watch directories rather than files so replacing a file by rename,
+    // as many editors do, is still seen
+    let mut targets = BTreeSet::new();
+    for path in layers.files() {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let (Ok(dir), Some(name)) = (dir.canonicalize(), path.file_name()) {
+            targets.insert(dir.join(name));
+        }
+    }
+    let dirs: BTreeSet<PathBuf> = targets
+        .iter()
+        .filter_map(|target| target.parent().map(Path::to_path_buf))
+        .collect();
+
+    let (config, value) = load(&layers, check)?;
+    let state = Arc::new(State {
+        layers,
+        check,
+        current: Mutex::new((Arc::new(config), value)),
+        subscribers: Mutex::new(Vec::new()),
+        reloading: Mutex::new(()),
+    });
+    if dirs.is_empty() {
+        return Ok(HotConfig {
+            state,
+            _watcher: None,
+        });
+    }
+
+    let (events, changed) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        let writes = match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+            EventKind::Access(_) | EventKind::Other | EventKind::Any => false,
+            _ => true,
+        };
+        if writes && event.paths.iter().any(|path| targets.contains(path)) {
+            let _ = events.send(());
+        }
+    })
+    .map_err(ReloadError::Watch)?;
+    for dir in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(ReloadError::Watch)?;
+    }
+
+    let weak: Weak<State<T>> = Arc::downgrade(&state);
+    std::thread::Builder::new()
+        .name("config-reload".to_string())
+        .spawn(move || {
+            while changed.recv().is_ok() {
+                while changed.recv_timeout(DEBOUNCE).is_ok() {}
+                let Some(state) = weak.upgrade() else { break };
+                state.reload();
+            }
+        })
+        .map_err(|error| ReloadError::Watch(error.into()))?;
+
+    Ok(HotConfig {
+        state,
+        _watcher: Some(watcher),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AuthConfig;
+    use crate::patterns::RateLimitConfig;
+    use futures::StreamExt;
+
+    async fn next<T>(changes: &mut AsyncStream<ConfigChange<T>>) -> ConfigChange<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        tokio::time::timeout(Duration::from_secs(10), changes.next())
+            .await
+            .expect("no reload within 10s")
+            .expect("change stream ended")
+    }
+
+    #[tokio::test]
+    async fn file_changes_reload_and_bad_configs_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("sugars-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("limits.json");
+        let write = |rpm: u32| {
+            let mut config = RateLimitConfig::simple(rpm);
+            config.burst_size = 5;
+            std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+        };
+        write(60);
+
+        let config = LayeredConfig::<RateLimitConfig>::new()
+            .file(&path)
+            .watch_validated()
+            .unwrap();
+        let mut changes = config.changes();
+        assert_eq!(config.current().requests_per_minute, 60);
+
+        write(120);
+        let change = next(&mut changes).await;
+        let ConfigChange::Updated {
+            config: updated,
+            diff,
+        } = change
+        else {
+            panic!("expected an update, got {change:?}");
+        };
+        assert_eq!(updated.requests_per_minute, 120);
+        assert_eq!(diff.to_string(), "~ /requests_per_minute: 60 -> 120");
+
+        write(0);
+        let change = next(&mut changes).await;
+        assert!(matches!(change.error(), Some(ReloadError::Invalid(_))));
+        assert_eq!(config.current().requests_per_minute, 120);
+
+        assert!(
+            config
+                .reload()
+                .is_some_and(|change| change.error().is_some())
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replaced_files_reload_without_showing_secrets() {
+        let dir = std::env::temp_dir().join(format!("sugars-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("auth.json");
+        let replace = |secret: &str| {
+            let staged = dir.join(".auth.json.tmp");
+            std::fs::write(&staged, serde_json::json!({ "secret": secret }).to_string()).unwrap();
+            std::fs::rename(&staged, &path).unwrap();
+        };
+        replace("first-secret");
+
+        let config = LayeredConfig::<AuthConfig>::new()
+            .defaults(&AuthConfig::jwt("default-secret"))
+            .file(&path)
+            .watch_validated()
+            .unwrap();
+        let mut changes = config.changes();
+
+        replace("second-secret");
+        let change = next(&mut changes).await;
+        let ConfigChange::Updated {
+            config: updated,
+            diff,
+        } = change
+        else {
+            panic!("expected an update, got {change:?}");
+        };
+        assert_eq!(updated.secret.as_ref().unwrap().expose(), "second-secret");
+        assert_eq!(
+            diff.to_string(),
+            "~ /secret: \"[REDACTED]\" -> \"[REDACTED]\""
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failing_first_load_is_returned() {
+        let dir = std::env::temp_dir().join(format!("sugars-first-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("limits.json");
+
+        std::fs::write(&path, "{ not json").unwrap();
+        let error = LayeredConfig::<RateLimitConfig>::new()
+            .file(&path)
+            .watch()
+            .unwrap_err();
+        assert!(matches!(error, ReloadError::Load(_)));
+
+        let config = RateLimitConfig::simple(0);
+        std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+        let error = LayeredConfig::<RateLimitConfig>::new()
+            .file(&path)
+            .watch_validated()
+            .unwrap_err();
+        assert!(matches!(error, ReloadError::Invalid(_)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
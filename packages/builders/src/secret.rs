@@ -0,0 +1,348 @@
+//! Secret configuration values
+//!
+//! [`Secret`] keeps passwords, tokens and keys out of logs and serialized
+//! configs. Its value is only reachable through [`Secret::expose`], and is
+//! zeroized when the secret is dropped.
+//!
+//! In config files a secret can point at where it lives instead of holding
+//! it inline:
+//!
+//! ```json
+//! { "password": "env:DB_PASSWORD", "api_key": "file:/run/secrets/api_key" }
+//! ```
+//!
+//! An inline value that itself starts with `env:` or `file:` is written
+//! with an `inline:` prefix, e.g. `"inline:env:not-a-reference"`; the prefix
+//! is dropped on load and added back when the value is serialized.
+//!
+//! Serializing a secret loaded that way writes the reference back out, so a
+//! config survives `to_json`/`from_json` without the value ever being
+//! written. A secret given inline serializes as `"[REDACTED]"`, which is
+//! rejected when read back rather than becoming the secret itself. Code
+//! that must keep the value, like [`LayeredConfig`](crate::LayeredConfig)
+//! layers and [`JsonConfig::to_json_with_secrets`](crate::JsonConfig::to_json_with_secrets),
+//! serializes inside [`exposed`] instead.
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// Placeholder written instead of a secret value
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Prefix marking a secret spec as the value itself, never a reference
+const INLINE: &str = "inline:";
+
+/// Whether an inline value would be read back as something else
+fn needs_escape(value: &str) -> bool {
+    ["env:", "file:", INLINE]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+        || value == REDACTED
+}
+
+thread_local! {
+    /// Set while [`exposed`] runs on this thread
+    static EXPOSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with inline secrets serializing as their values
+///
+/// Secrets loaded from `env:`/`file:` still serialize as their reference.
+/// Only the current thread is affected.
+pub(crate) fn exposed<R>(f: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            EXPOSED.with(|exposed| exposed.set(self.0));
+        }
+    }
+    let _restore = Restore(EXPOSED.with(|exposed| exposed.replace(true)));
+    f()
+}
+
+/// Why a secret could not be loaded
+#[derive(Debug)]
+pub enum SecretError {
+    /// The environment variable is unset or not unicode
+    Env {
+        /// Variable name
+        name: String,
+        /// Underlying error
+        error: std::env::VarError,
+    },
+    /// The file could not be read
+    File {
+        /// File path
+        path: PathBuf,
+        /// Underlying error
+        error: std::io::Error,
+    },
+    /// The value is the redaction placeholder, not a secret
+    Redacted,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::Env { name, error } => {
+                write!(f, "cannot read secret from env var `{name}`: {error}")
+            }
+            SecretError::File { path, error } => {
+                write!(f, "cannot read secret from `{}`: {error}", path.display())
+            }
+            SecretError::Redacted => {
+                write!(
+                    f,
+                    "secret is the `{REDACTED}` placeholder; set the real value"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SecretError::Env { error, .. } => Some(error),
+            SecretError::File { error, .. } => Some(error),
+            SecretError::Redacted => None,
+        }
+    }
+}
+
+/// A value that is redacted in `Debug`, `Display` and `Serialize`, and
+/// zeroized on drop
+pub struct Secret<T: Zeroize> {
+    value: T,
+    /// `env:NAME` or `file:PATH` the value was loaded from
+    source: Option<String>,
+}
+
+impl<T: Zeroize> Secret<T> {
+    /// Wrap a value held inline
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            source: None,
+        }
+    }
+
+    /// The secret value
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    /// Where the value was loaded from, as `env:NAME` or `file:PATH`
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+}
+
+impl Secret<String> {
+    /// Load a secret written as `env:NAME`, `file:PATH` or an inline value
+    ///
+    /// `inline:VALUE` is always the inline value `VALUE`, even if it starts
+    /// with `env:` or `file:`.
+    pub fn load(spec: &str) -> Result<Self, SecretError> {
+        if let Some(value) = spec.strip_prefix(INLINE) {
+            Ok(Self::new(value.to_string()))
+        } else if let Some(name) = spec.strip_prefix("env:") {
+            Self::from_env(name)
+        } else if let Some(path) = spec.strip_prefix("file:") {
+            Self::from_file(path)
+        } else if spec == REDACTED {
+            Err(SecretError::Redacted)
+        } else {
+            Ok(Self::new(spec.to_string()))
+        }
+    }
+
+    /// Read a secret from an environment variable
+    pub fn from_env(name: &str) -> Result<Self, SecretError> {
+        let value = std::env::var(name).map_err(|error| SecretError::Env {
+            name: name.to_string(),
+            error,
+        })?;
+        Ok(Self {
+            value,
+            source: Some(format!("env:{name}")),
+        })
+    }
+
+    /// Read a secret from a file, dropping a trailing newline
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SecretError> {
+        let path = path.as_ref();
+        let mut value = std::fs::read_to_string(path).map_err(|error| SecretError::File {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let len = value.trim_end_matches(['\n', '\r']).len();
+        value.truncate(len);
+        Ok(Self {
+            value,
+            source: Some(format!("file:{}", path.display())),
+        })
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = self.source.as_deref().unwrap_or(REDACTED);
+        f.debug_tuple("Secret")
+            .field(&format_args!("{shown}"))
+            .finish()
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: Zeroize + Serialize + 'static> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match &self.source {
+            Some(source) => ser.serialize_str(source),
+            None if EXPOSED.with(Cell::get) => {
+                let text = (&self.value as &dyn Any).downcast_ref::<String>();
+                match text.filter(|text| needs_escape(text)) {
+                    Some(text) => {
+                        let mut escaped = format!("{INLINE}{text}");
+                        let result = ser.serialize_str(&escaped);
+                        escaped.zeroize();
+                        result
+                    }
+                    None => self.value.serialize(ser),
+                }
+            }
+            None => ser.serialize_str(REDACTED),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret<String> {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let mut spec = String::deserialize(de)?;
+        let secret = Secret::load(&spec).map_err(de::Error::custom);
+        spec.zeroize();
+        secret
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<T: Zeroize> schemars::JsonSchema for Secret<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Secret".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": "Secret value, or `env:NAME` / `file:PATH` to load it; `inline:VALUE` is always the value itself",
+            "writeOnly": true
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JsonConfig;
+
+    #[test]
+    fn secrets_never_print_or_serialize() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(
+            format!("{secret} {secret:?}"),
+            "[REDACTED] Secret([REDACTED])"
+        );
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"[REDACTED]\"");
+
+        let error = serde_json::from_str::<Secret<String>>("\"[REDACTED]\"").unwrap_err();
+        assert!(error.to_string().contains("placeholder"));
+    }
+
+    #[test]
+    fn inline_secrets_survive_internal_round_trips() {
+        let database = crate::Database {
+            host: "db.internal".to_string(),
+            port: "5432".to_string(),
+            database: "app".to_string(),
+            user: "app".to_string(),
+            password: Some(Secret::from("hunter2")),
+        };
+        assert!(!database.to_json().unwrap().contains("hunter2"));
+
+        let json = database.to_json_with_secrets().unwrap();
+        let restored = <crate::Database as JsonConfig>::from_json(&json).unwrap();
+        assert_eq!(restored.password.unwrap().expose(), "hunter2");
+        assert_eq!(
+            serde_json::to_string(&Secret::from("hunter2")).unwrap(),
+            "\"[REDACTED]\""
+        );
+    }
+
+    #[test]
+    fn references_load_and_round_trip() {
+        let path = std::env::temp_dir().join(format!("sugars-secret-{}", std::process::id()));
+        std::fs::write(&path, "s3cret\n").unwrap();
+        let spec = format!("file:{}", path.display());
+
+        let secret: Secret<String> = serde_json::from_value(spec.clone().into()).unwrap();
+        assert_eq!(secret.expose(), "s3cret");
+        assert_eq!(serde_json::to_value(&secret).unwrap(), spec);
+        std::fs::remove_file(&path).unwrap();
+
+        let Err(SecretError::Env { name, .. }) = Secret::load("env:SUGARS_UNSET_SECRET") else {
+            panic!("expected an env error");
+        };
+        assert_eq!(name, "SUGARS_UNSET_SECRET");
+
+        let literal = Secret::load("inline:env:SUGARS_UNSET_SECRET").unwrap();
+        assert_eq!(literal.expose(), "env:SUGARS_UNSET_SECRET");
+        assert_eq!(literal.source(), None);
+        let json = exposed(|| serde_json::to_value(&literal)).unwrap();
+        assert_eq!(json, "inline:env:SUGARS_UNSET_SECRET");
+        let restored: Secret<String> = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.expose(), "env:SUGARS_UNSET_SECRET");
+    }
+}
@@ -0,0 +1,321 @@
+//! Builder state management
+//!
+//! [`Incomplete`], [`Complete`] and [`Validated`] describe a builder as a
+//! whole. When a builder has several independent required fields, its state
+//! is instead a tuple with one [`Missing`] or [`Has`] slot per field, and
+//! setting a field flips its slot:
+//!
+//! ```
+//! use sugars_builders::state::{AllSet, Has, Missing, Set, Tracked};
+//!
+//! pub struct Endpoint;
+//! pub struct Auth;
+//!
+//! #[derive(Default)]
+//! struct Draft {
+//!     endpoint: String,
+//!     auth: String,
+//! }
+//!
+//! struct ClientBuilder<S = sugars_builders::missing![Endpoint, Auth]>(Tracked<Draft, S>);
+//!
+//! impl ClientBuilder {
+//!     fn new() -> Self {
+//!         Self(Tracked::new(Draft::default()))
+//!     }
+//! }
+//!
+//! impl<S> ClientBuilder<S> {
+//!     fn endpoint<I>(self, url: &str) -> ClientBuilder<S::Output>
+//!     where
+//!         S: Set<Endpoint, I>,
+//!     {
+//!         ClientBuilder(self.0.set(|draft| draft.endpoint = url.into()))
+//!     }
+//!
+//!     fn auth<I>(self, token: &str) -> ClientBuilder<S::Output>
+//!     where
+//!         S: Set<Auth, I>,
+//!     {
+//!         ClientBuilder(self.0.set(|draft| draft.auth = token.into()))
+//!     }
+//!
+//!     fn build(self) -> String
+//!     where
+//!         S: AllSet,
+//!     {
+//!         let draft = self.0.finish();
+//!         format!("{} ({})", draft.endpoint, draft.auth)
+//!     }
+//! }
+//!
+//! let half: ClientBuilder<(Has<Endpoint>, Missing<Auth>)> =
+//!     ClientBuilder::new().endpoint("https://api.example.com");
+//! assert_eq!(half.auth("token").build(), "https://api.example.com (token)");
+//! ```
+//!
+//! Calling `build` before `auth` does not compile, and the error names the
+//! slot that is still `Missing<Auth>`. Put the [`AllSet`] bound on the
+//! method rather than on an `impl` block; rustc then reports the missing
+//! slot directly instead of "method exists but its trait bounds were not
+//! satisfied".
+
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Marker trait for builder states
+pub trait BuilderState {}
+
+/// Builder is incomplete and missing required fields
+pub struct Incomplete;
+impl BuilderState for Incomplete {}
+
+/// Builder has all required fields
+pub struct Complete;
+impl BuilderState for Complete {}
+
+/// Builder with custom validation state
+pub struct Validated<T>(PhantomData<T>);
+impl<T> BuilderState for Validated<T> {}
+
+/// State transition helpers
+pub trait StateTransition<To: BuilderState> {
+    /// The output type after transition.
+    type Output;
+    /// Performs the state transition.
+    fn transition(self) -> Self::Output;
+}
+
+/// Slot for a required field `F` that has been set
+pub struct Has<F>(PhantomData<fn() -> F>);
+
+/// Slot for a required field `F` that has not been set yet
+pub struct Missing<F>(PhantomData<fn() -> F>);
+
+/// A slot for field `F`, set or not
+#[diagnostic::on_unimplemented(
+    message = "`{F}` is not a required field of this builder",
+    label = "`{Self}` is the slot for another field"
+)]
+pub trait Slot<F> {}
+impl<F> Slot<F> for Has<F> {}
+impl<F> Slot<F> for Missing<F> {}
+
+/// A slot whose field has been set
+#[diagnostic::on_unimplemented(
+    message = "required builder field is not set: `{Self}`",
+    label = "`{Self}` must be set before this call",
+    note = "call the setter for this field first"
+)]
+pub trait IsSet {}
+impl<F> IsSet for Has<F> {}
+
+/// A slot whose field has not been set
+#[diagnostic::on_unimplemented(
+    message = "builder must start with every field missing, found `{Self}`",
+    label = "this slot is already set"
+)]
+pub trait IsMissing {}
+impl<F> IsMissing for Missing<F> {}
+
+/// Field-slot tuple whose every slot is [`Has`]; bound `build()` on this
+#[diagnostic::on_unimplemented(
+    message = "not every required builder field is set in `{Self}`",
+    note = "each `Missing<Field>` slot still needs its setter called"
+)]
+pub trait AllSet {}
+
+/// Field-slot tuple whose every slot is [`Missing`]; a builder's start state
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a fresh builder state",
+    note = "a builder starts with every slot `Missing<Field>`"
+)]
+pub trait NoneSet {}
+
+/// Positions in a field-slot tuple
+///
+/// These fill the index parameter of [`Set`]. The compiler infers them, so
+/// they never need to be written out.
+pub mod index {
+    /// First slot
+    pub struct P0;
+    /// Second slot
+    pub struct P1;
+    /// Third slot
+    pub struct P2;
+    /// Fourth slot
+    pub struct P3;
+    /// Fifth slot
+    pub struct P4;
+    /// Sixth slot
+    pub struct P5;
+    /// Seventh slot
+    pub struct P6;
+    /// Eighth slot
+    pub struct P7;
+}
+
+/// Field-slot tuple with a slot for `F` at position `I`
+///
+/// `Output` is the same tuple with that slot [`Has<F>`]. Setting a field
+/// that is already set is allowed and keeps it set.
+#[diagnostic::on_unimplemented(
+    message = "`{F}` is not a required field of this builder",
+    label = "no `Missing<{F}>` or `Has<{F}>` slot in `{Self}`"
+)]
+pub trait Set<F, I> {
+    /// The state after setting `F`
+    type Output;
+}
+
+macro_rules! field_sets {
+    ($($slot:ident)+) => {
+        impl<$($slot: IsSet),+> AllSet for ($($slot,)+) {}
+        impl<$($slot: IsMissing),+> NoneSet for ($($slot,)+) {}
+        field_sets!(@set [$($slot)+] [] [$($slot)+] [P0 P1 P2 P3 P4 P5 P6 P7]);
+    };
+    (@set [$($all:ident)+] [$($before:ident)*] [$at:ident $($after:ident)*] [$index:ident $($rest:ident)*]) => {
+        impl<Field, $($all),+> Set<Field, index::$index> for ($($all,)+)
+        where
+            $at: Slot<Field>,
+        {
+            type Output = ($($before,)* Has<Field>, $($after,)*);
+        }
+        field_sets!(@set [$($all)+] [$($before)* $at] [$($after)*] [$($rest)*]);
+    };
+    (@set [$($all:ident)+] [$($before:ident)*] [] [$($rest:ident)*]) => {};
+}
+
+field_sets!(A);
+field_sets!(A B);
+field_sets!(A B C);
+field_sets!(A B C D);
+field_sets!(A B C D E);
+field_sets!(A B C D E G);
+field_sets!(A B C D E G H);
+field_sets!(A B C D E G H J);
+
+impl AllSet for () {}
+impl NoneSet for () {}
+
+/// A value being built, with its required fields tracked in `S`
+///
+/// `S` is a tuple of [`Missing`]/[`Has`] slots, up to eight. A builder
+/// wraps one of these and re-wraps the result of [`set`](Self::set) in each
+/// setter; see the [module docs](self).
+pub struct Tracked<T, S> {
+    value: T,
+    state: PhantomData<fn() -> S>,
+}
+
+impl<T, S: NoneSet> Tracked<T, S> {
+    /// Start tracking `value` with every field missing
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<T, S> Tracked<T, S> {
+    /// Apply `update` and mark field `F` as set
+    pub fn set<F, I>(mut self, update: impl FnOnce(&mut T)) -> Tracked<T, S::Output>
+    where
+        S: Set<F, I>,
+    {
+        update(&mut self.value);
+        Tracked {
+            value: self.value,
+            state: PhantomData,
+        }
+    }
+
+    /// Change the value without touching any required field
+    pub fn update(mut self, update: impl FnOnce(&mut T)) -> Self {
+        update(&mut self.value);
+        self
+    }
+
+    /// The value so far
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// The value, once every required field is set
+    pub fn finish(self) -> T
+    where
+        S: AllSet,
+    {
+        self.value
+    }
+}
+
+impl<T: Clone, S> Clone for Tracked<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for Tracked<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracked")
+            .field("value", &self.value)
+            .field("state", &std::any::type_name::<S>())
+            .finish()
+    }
+}
+
+/// Start state for a builder: every listed field [`Missing`]
+///
+/// `missing![Endpoint, Auth]` is `(Missing<Endpoint>, Missing<Auth>)`.
+#[macro_export]
+macro_rules! missing {
+    ($($field:ty),+ $(,)?) => {
+        ($($crate::state::Missing<$field>,)+)
+    };
+}
+
+/// Done state for a builder: every listed field [`Has`]
+///
+/// `has![Endpoint, Auth]` is `(Has<Endpoint>, Has<Auth>)`.
+#[macro_export]
+macro_rules! has {
+    ($($field:ty),+ $(,)?) => {
+        ($($crate::state::Has<$field>,)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Name;
+    struct Port;
+    struct Host;
+
+    type Start = missing![Name, Port, Host];
+
+    fn assert_all_set<S: AllSet>() {}
+
+    #[test]
+    fn setting_fields_in_any_order_completes_the_state() {
+        let tracked: Tracked<Vec<&str>, Start> = Tracked::new(Vec::new());
+        let tracked = tracked
+            .set::<Port, _>(|v| v.push("port"))
+            .update(|v| v.push("untracked"))
+            .set::<Host, _>(|v| v.push("host"));
+        let tracked: Tracked<_, (Missing<Name>, Has<Port>, Has<Host>)> = tracked;
+        let tracked = tracked.set::<Name, _>(|v| v.push("name"));
+        let tracked = tracked.set::<Port, _>(|v| v.push("port again"));
+
+        assert_all_set::<has![Name, Port, Host]>();
+        assert_eq!(
+            tracked.finish(),
+            ["port", "untracked", "host", "name", "port again"]
+        );
+    }
+}
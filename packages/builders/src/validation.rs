@@ -0,0 +1,791 @@
+//! Declarative validation for configuration types
+//!
+//! A type implements [`Validate`] by listing its fields and the [`Rule`]s
+//! each must satisfy. Every rule runs, and every failure is collected into
+//! one [`ValidationErrors`] report addressed by JSON pointer:
+//!
+//! ```ignore
+//! impl Validate for ServerConfig {
+//!     fn validate(&self, v: &mut Validator) {
+//!         v.field("host", &self.host, [non_empty()])
+//!             .field("port", &self.port, [range(1..=65535)])
+//!             .each("upstreams", self.upstreams.iter(), &[url()])
+//!             .nested("tls", &self.tls);
+//!     }
+//! }
+//!
+//! // Only a draft that passed validation can be built
+//! let config = Draft::new(config).validate()?.build();
+//! ```
+
+use crate::patterns::{AuthConfig, CorsConfig, RateLimitConfig};
+use crate::state::{BuilderState, Incomplete, StateTransition, Validated};
+use crate::{ConfigBuilder, HashMap};
+use std::borrow::Borrow;
+use std::collections::HashMap as StdHashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use sugars_collections::{OneOrMany, ZeroOneOrMany};
+
+/// One failed rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// JSON pointer to the offending value, e.g. `/server/port`
+    pub pointer: String,
+    /// Name of the rule that failed, e.g. `range`
+    pub rule: &'static str,
+    /// Human readable explanation
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{pointer}: {}", self.message)
+    }
+}
+
+/// Every failed rule of a validation run, in the order they were checked
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors(Vec<ValidationError>);
+
+impl ValidationErrors {
+    /// Number of failures
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if nothing failed
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the failures
+    pub fn iter(&self) -> std::slice::Iter<'_, ValidationError> {
+        self.0.iter()
+    }
+
+    /// Failures for the value at `pointer`
+    pub fn at<'a>(&'a self, pointer: &'a str) -> impl Iterator<Item = &'a ValidationError> + 'a {
+        self.0.iter().filter(move |error| error.pointer == pointer)
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl IntoIterator for ValidationErrors {
+    type Item = ValidationError;
+    type IntoIter = std::vec::IntoIter<ValidationError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+type Check<V> = Box<dyn Fn(&V) -> Result<(), String> + Send + Sync>;
+
+/// A named check applied to one value
+pub struct Rule<V: ?Sized> {
+    name: &'static str,
+    check: Check<V>,
+}
+
+impl<V: ?Sized> Rule<V> {
+    /// Name reported when the rule fails
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Run the rule on its own
+    pub fn check(&self, value: &V) -> Result<(), String> {
+        (self.check)(value)
+    }
+}
+
+impl<V: ?Sized> fmt::Debug for Rule<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Rule").field(&self.name).finish()
+    }
+}
+
+/// Value must lie within `bounds`
+pub fn range<V, R>(bounds: R) -> Rule<V>
+where
+    V: PartialOrd + fmt::Display + 'static,
+    R: RangeBounds<V> + Send + Sync + 'static,
+{
+    Rule {
+        name: "range",
+        check: Box::new(move |value| {
+            if bounds.contains(value) {
+                return Ok(());
+            }
+            let lower = match bounds.start_bound() {
+                Bound::Included(start) => Some(format!("at least {start}")),
+                Bound::Excluded(start) => Some(format!("greater than {start}")),
+                Bound::Unbounded => None,
+            };
+            let upper = match bounds.end_bound() {
+                Bound::Included(end) => Some(format!("at most {end}")),
+                Bound::Excluded(end) => Some(format!("less than {end}")),
+                Bound::Unbounded => None,
+            };
+            let expected = [lower, upper].into_iter().flatten().collect::<Vec<_>>();
+            Err(format!("must be {}, got {value}", expected.join(" and ")))
+        }),
+    }
+}
+
+/// Values that can be empty, for [`non_empty`]
+pub trait HasLength {
+    /// Number of elements (or bytes, for strings)
+    fn length(&self) -> usize;
+
+    /// Whether [`non_empty`] should reject the value
+    fn is_blank(&self) -> bool {
+        self.length() == 0
+    }
+}
+
+impl HasLength for str {
+    fn length(&self) -> usize {
+        self.len()
+    }
+
+    /// Whitespace-only strings count as empty
+    fn is_blank(&self) -> bool {
+        self.trim().is_empty()
+    }
+}
+
+impl HasLength for String {
+    fn length(&self) -> usize {
+        self.len()
+    }
+
+    fn is_blank(&self) -> bool {
+        self.as_str().is_blank()
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> HasLength for ZeroOneOrMany<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> HasLength for OneOrMany<T> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> HasLength for HashMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> HasLength for StdHashMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Value must not be empty; strings must contain more than whitespace
+pub fn non_empty<V: HasLength + ?Sized>() -> Rule<V> {
+    Rule {
+        name: "non_empty",
+        check: Box::new(|value| {
+            if !value.is_blank() {
+                Ok(())
+            } else {
+                Err("must not be empty".to_string())
+            }
+        }),
+    }
+}
+
+/// Value must be an absolute URL such as `https://api.example.com/v1`
+///
+/// Checks the shape only: a scheme followed by `://` and a host.
+pub fn url<V: AsRef<str> + ?Sized>() -> Rule<V> {
+    Rule {
+        name: "url",
+        check: Box::new(|value| {
+            let value = value.as_ref();
+            let valid = value.split_once("://").is_some_and(|(scheme, rest)| {
+                let mut chars = scheme.chars();
+                chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                    && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                    && rest
+                        .split(['/', '?', '#'])
+                        .next()
+                        .is_some_and(|host| !host.is_empty() && !host.contains(char::is_whitespace))
+            });
+            if valid {
+                Ok(())
+            } else {
+                Err(format!("must be an absolute URL, got `{value}`"))
+            }
+        }),
+    }
+}
+
+/// Value must match `pattern` somewhere; anchor it with `^...$` to match
+/// the whole value
+///
+/// # Panics
+///
+/// Panics if `pattern` is not a valid regular expression.
+#[cfg(feature = "regex")]
+pub fn regex<V: AsRef<str> + ?Sized>(pattern: &str) -> Rule<V> {
+    let regex = regex::Regex::new(pattern).expect("invalid validation regex");
+    Rule {
+        name: "regex",
+        check: Box::new(move |value| {
+            if regex.is_match(value.as_ref()) {
+                Ok(())
+            } else {
+                Err(format!("must match `{}`", regex.as_str()))
+            }
+        }),
+    }
+}
+
+/// Value must equal one of `allowed`
+pub fn one_of<V, A>(allowed: impl IntoIterator<Item = A>) -> Rule<V>
+where
+    V: PartialEq + fmt::Debug + ?Sized,
+    A: Borrow<V> + fmt::Debug + Send + Sync + 'static,
+{
+    let allowed: Vec<A> = allowed.into_iter().collect();
+    Rule {
+        name: "one_of",
+        check: Box::new(move |value| {
+            if allowed.iter().any(|candidate| value == candidate.borrow()) {
+                Ok(())
+            } else {
+                Err(format!("must be one of {allowed:?}, got {value:?}"))
+            }
+        }),
+    }
+}
+
+/// Any check; return `Err(message)` to fail
+pub fn custom<V, F>(name: &'static str, check: F) -> Rule<V>
+where
+    V: ?Sized,
+    F: Fn(&V) -> Result<(), String> + Send + Sync + 'static,
+{
+    Rule {
+        name,
+        check: Box::new(check),
+    }
+}
+
+/// Types that describe their own validation rules
+pub trait Validate {
+    /// Check every field, reporting failures to `validator`
+    fn validate(&self, validator: &mut Validator);
+
+    /// Run [`validate`](Self::validate) and collect the report
+    fn validation(&self) -> Result<(), ValidationErrors> {
+        let mut validator = Validator::new();
+        self.validate(&mut validator);
+        validator.finish()
+    }
+}
+
+/// Collects failures while a [`Validate`] implementation walks its fields
+#[derive(Debug, Default)]
+pub struct Validator {
+    prefix: String,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    /// Start an empty report rooted at `/`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check `value`, found at `name` under the current path, against `rules`
+    pub fn field<V: ?Sized>(
+        &mut self,
+        name: &str,
+        value: &V,
+        rules: impl IntoIterator<Item = Rule<V>>,
+    ) -> &mut Self {
+        let pointer = self.pointer(name);
+        for rule in rules {
+            self.check(&pointer, value, &rule);
+        }
+        self
+    }
+
+    /// Check every item of a list against `rules`, addressing items by index
+    pub fn each<'a, V>(
+        &mut self,
+        name: &str,
+        values: impl IntoIterator<Item = &'a V>,
+        rules: &[Rule<V>],
+    ) -> &mut Self
+    where
+        V: ?Sized + 'a,
+    {
+        let list = self.pointer(name);
+        for (index, value) in values.into_iter().enumerate() {
+            let pointer = format!("{list}/{index}");
+            for rule in rules {
+                self.check(&pointer, value, rule);
+            }
+        }
+        self
+    }
+
+    /// Validate a nested value under `name`
+    pub fn nested<T: Validate + ?Sized>(&mut self, name: &str, value: &T) -> &mut Self {
+        let parent = std::mem::take(&mut self.prefix);
+        self.prefix = format!("{parent}/{}", escape(name));
+        value.validate(self);
+        self.prefix = parent;
+        self
+    }
+
+    /// Validate every nested value of a list, addressing items by index
+    pub fn nested_each<'a, T>(
+        &mut self,
+        name: &str,
+        values: impl IntoIterator<Item = &'a T>,
+    ) -> &mut Self
+    where
+        T: Validate + ?Sized + 'a,
+    {
+        let parent = std::mem::take(&mut self.prefix);
+        let list = format!("{parent}/{}", escape(name));
+        for (index, value) in values.into_iter().enumerate() {
+            self.prefix = format!("{list}/{index}");
+            value.validate(self);
+        }
+        self.prefix = parent;
+        self
+    }
+
+    /// Record a failure that no single rule expresses, e.g. across fields
+    pub fn error(&mut self, name: &str, message: impl Into<String>) -> &mut Self {
+        let pointer = self.pointer(name);
+        self.errors.push(ValidationError {
+            pointer,
+            rule: "custom",
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Finish, returning every failure found
+    pub fn finish(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.errors))
+        }
+    }
+
+    fn check<V: ?Sized>(&mut self, pointer: &str, value: &V, rule: &Rule<V>) {
+        if let Err(message) = rule.check(value) {
+            self.errors.push(ValidationError {
+                pointer: pointer.to_string(),
+                rule: rule.name,
+                message,
+            });
+        }
+    }
+
+    fn pointer(&self, name: &str) -> String {
+        format!("{}/{}", self.prefix, escape(name))
+    }
+}
+
+/// Escape a key for use in a JSON pointer (RFC 6901)
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// A config on its way to being built
+///
+/// `build` is only available once [`validate`](Draft::validate) has moved
+/// the draft into the [`Validated`] state; editing a validated draft moves
+/// it back.
+pub struct Draft<T, S: BuilderState = Incomplete> {
+    config: T,
+    _state: PhantomData<S>,
+}
+
+impl<T, S: BuilderState> Draft<T, S> {
+    /// The config as it stands
+    pub fn get(&self) -> &T {
+        &self.config
+    }
+}
+
+impl<T: Clone, S: BuilderState> Clone for Draft<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Debug, S: BuilderState> fmt::Debug for Draft<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Draft")
+            .field("config", &self.config)
+            .field("state", &std::any::type_name::<S>())
+            .finish()
+    }
+}
+
+impl<T> Draft<T, Incomplete> {
+    /// Start a draft from a config that has not been validated
+    pub fn new(config: T) -> Self {
+        Self {
+            config,
+            _state: PhantomData,
+        }
+    }
+
+    /// Change the config before validating it
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.config
+    }
+}
+
+impl<T: Validate> Draft<T, Incomplete> {
+    /// Validate every field, keeping the config only if all rules pass
+    pub fn validate(self) -> Result<Draft<T, Validated<T>>, ValidationErrors> {
+        self.config.validation()?;
+        Ok(Draft {
+            config: self.config,
+            _state: PhantomData,
+        })
+    }
+}
+
+impl<T> Draft<T, Validated<T>> {
+    /// Take the validated config
+    pub fn build(self) -> T {
+        self.config
+    }
+
+    /// Go back to editing; the draft must be validated again
+    pub fn edit(self) -> Draft<T, Incomplete> {
+        Draft::new(self.config)
+    }
+}
+
+impl<T: Validate> StateTransition<Validated<T>> for Draft<T, Incomplete> {
+    type Output = Result<Draft<T, Validated<T>>, ValidationErrors>;
+
+    fn transition(self) -> Self::Output {
+        self.validate()
+    }
+}
+
+impl<T: Validate> ConfigBuilder<T> for Draft<T, Incomplete> {
+    type Error = ValidationErrors;
+
+    fn build(self) -> Result<T, ValidationErrors> {
+        self.validate().map(Draft::build)
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.config.validation()
+    }
+}
+
+impl Validate for RateLimitConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.field(
+            "requests_per_minute",
+            &self.requests_per_minute,
+            [range(1..)],
+        )
+        .each(
+            "exclude_paths",
+            self.exclude_paths.iter().map(String::as_str),
+            &[path()],
+        )
+        .nested("custom_rules", &CustomRules(&self.custom_rules));
+    }
+}
+
+/// [`RateLimitConfig::custom_rules`], each entry addressed by its route
+struct CustomRules<'a>(&'a crate::HashMap<String, u32>);
+
+impl Validate for CustomRules<'_> {
+    fn validate(&self, v: &mut Validator) {
+        // Sorted so errors come out in the same order every time
+        let mut rules: Vec<_> = self.0.iter().collect();
+        rules.sort();
+        for (route, limit) in rules {
+            v.nested(route, &CustomRule(route, *limit));
+        }
+    }
+}
+
+/// One entry of [`RateLimitConfig::custom_rules`]
+struct CustomRule<'a>(&'a str, u32);
+
+impl Validate for CustomRule<'_> {
+    fn validate(&self, v: &mut Validator) {
+        let CustomRule(route, limit) = *self;
+        v.field("path", route, [path()])
+            .field("limit", &limit, [range(1..)]);
+    }
+}
+
+impl Validate for CorsConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.each(
+            "allowed_origins",
+            self.allowed_origins.iter().map(String::as_str),
+            &[custom("origin", |origin: &str| {
+                if origin == "*" {
+                    Ok(())
+                } else {
+                    url().check(origin)
+                }
+            })],
+        )
+        .each(
+            "allowed_methods",
+            self.allowed_methods.iter().map(String::as_str),
+            &[one_of(HTTP_METHODS)],
+        );
+        if self.credentials && self.allowed_origins.iter().any(|origin| origin == "*") {
+            v.error(
+                "allowed_origins",
+                "must list explicit origins when credentials are allowed",
+            );
+        }
+    }
+}
+
+impl Validate for AuthConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.field("token_ttl", &self.token_ttl, [range(1..)])
+            .each(
+                "methods",
+                self.methods.iter().map(String::as_str),
+                &[non_empty()],
+            )
+            .each(
+                "providers",
+                self.providers.iter().map(String::as_str),
+                &[non_empty()],
+            );
+        // Alongside oauth, tokens are issued and signed by the providers
+        let uses = |name: &str| self.methods.iter().any(|method| method == name);
+        if uses("jwt") && !uses("oauth") && self.secret.is_none() {
+            v.error("secret", "is required for the jwt method");
+        }
+    }
+}
+
+const HTTP_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+/// Route paths must be absolute
+fn path() -> Rule<str> {
+    custom("path", |path: &str| {
+        if path.starts_with('/') {
+            Ok(())
+        } else {
+            Err(format!("must start with `/`, got `{path}`"))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Upstream {
+        url: String,
+        weight: u32,
+    }
+
+    impl Validate for Upstream {
+        fn validate(&self, v: &mut Validator) {
+            v.field("url", &self.url, [url()])
+                .field("weight", &self.weight, [range(1..=100)]);
+        }
+    }
+
+    #[derive(Debug)]
+    struct Proxy {
+        name: String,
+        mode: String,
+        upstreams: Vec<Upstream>,
+        tags: ZeroOneOrMany<String>,
+    }
+
+    impl Validate for Proxy {
+        fn validate(&self, v: &mut Validator) {
+            v.field("name", &self.name, [non_empty()])
+                .field(
+                    "mode",
+                    self.mode.as_str(),
+                    [one_of(["round_robin", "random"])],
+                )
+                .each("tags", self.tags.iter(), &[non_empty()])
+                .nested_each("upstreams", &self.upstreams);
+        }
+    }
+
+    #[test]
+    fn every_failure_is_reported_by_pointer() {
+        let proxy = Proxy {
+            name: " ".to_string(),
+            mode: "sticky".to_string(),
+            upstreams: vec![
+                Upstream {
+                    url: "https://a.internal".to_string(),
+                    weight: 10,
+                },
+                Upstream {
+                    url: "a.internal".to_string(),
+                    weight: 0,
+                },
+            ],
+            tags: ZeroOneOrMany::many(vec!["edge".to_string(), String::new()]),
+        };
+
+        let errors = Draft::new(proxy).validate().unwrap_err();
+        let failed: Vec<_> = errors
+            .iter()
+            .map(|error| (error.pointer.as_str(), error.rule))
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                ("/name", "non_empty"),
+                ("/mode", "one_of"),
+                ("/tags/1", "non_empty"),
+                ("/upstreams/1/url", "url"),
+                ("/upstreams/1/weight", "range"),
+            ]
+        );
+        assert_eq!(
+            errors.at("/upstreams/1/weight").next().unwrap().message,
+            "must be at least 1 and at most 100, got 0"
+        );
+        assert_eq!(" ".length(), 1);
+    }
+
+    #[test]
+    fn only_validated_drafts_build() {
+        let upstream = Upstream {
+            url: "https://a.internal".to_string(),
+            weight: 5,
+        };
+        let built = Draft::new(upstream).validate().unwrap().build();
+        assert_eq!(built.weight, 5);
+
+        let mut draft = Draft::new(built);
+        draft.get_mut().weight = 500;
+        assert_eq!(ConfigBuilder::build(draft).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn patterns_check_their_fields() {
+        assert!(RateLimitConfig::simple(600).validation().is_ok());
+        assert!(CorsConfig::permissive().validation().is_ok());
+
+        let mut limits = RateLimitConfig::simple(600);
+        limits.custom_rules.insert("/search".to_string(), 0);
+        limits.custom_rules.insert("api".to_string(), 0);
+        let errors = limits.validation().unwrap_err();
+        let pointers: Vec<_> = errors.iter().map(|error| error.pointer.as_str()).collect();
+        assert_eq!(
+            pointers,
+            vec![
+                "/custom_rules/~1search/limit",
+                "/custom_rules/api/path",
+                "/custom_rules/api/limit"
+            ]
+        );
+
+        let mut cors = CorsConfig::strict(
+            ZeroOneOrMany::many(vec!["*".to_string(), "example.com".to_string()]),
+            OneOrMany::one("FETCH".to_string()),
+            ZeroOneOrMany::none(),
+        );
+        cors.credentials = true;
+        let errors = cors.validation().unwrap_err();
+        let pointers: Vec<_> = errors.iter().map(|error| error.pointer.as_str()).collect();
+        assert_eq!(
+            pointers,
+            vec![
+                "/allowed_origins/1",
+                "/allowed_methods/0",
+                "/allowed_origins"
+            ]
+        );
+    }
+
+    #[test]
+    fn auth_secrets_are_required_only_for_local_jwt() {
+        assert!(AuthConfig::oauth("github".to_string()).validation().is_ok());
+        assert!(AuthConfig::jwt("s3cret").validation().is_ok());
+
+        let mut jwt = AuthConfig::jwt("s3cret");
+        jwt.secret = None;
+        let errors = jwt.validation().unwrap_err();
+        assert_eq!(errors.at("/secret").count(), 1);
+
+        let legacy: AuthConfig = serde_json::from_value(serde_json::json!({
+            "methods": "jwt",
+            "token_ttl": 3600,
+            "providers": null,
+            "settings": { "algorithm": "HS256", "secret": "s3cret" }
+        }))
+        .unwrap();
+        assert_eq!(legacy.secret.as_ref().unwrap().expose(), "s3cret");
+        assert!(!legacy.settings.contains_key("secret"));
+        assert!(legacy.validation().is_ok());
+    }
+}
@@ -0,0 +1,7 @@
+//! Compile errors for builders with required-field tracking
+
+#[test]
+fn missing_fields_are_compile_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
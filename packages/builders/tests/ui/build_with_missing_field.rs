@@ -0,0 +1,28 @@
+use sugars_builders::missing;
+use sugars_builders::state::{AllSet, Set, Tracked};
+
+struct Endpoint;
+struct Auth;
+
+struct ClientBuilder<S = missing![Endpoint, Auth]>(Tracked<String, S>);
+
+impl<S> ClientBuilder<S> {
+    fn endpoint<I>(self, url: &str) -> ClientBuilder<S::Output>
+    where
+        S: Set<Endpoint, I>,
+    {
+        ClientBuilder(self.0.set(|draft| draft.push_str(url)))
+    }
+
+    fn build(self) -> String
+    where
+        S: AllSet,
+    {
+        self.0.finish()
+    }
+}
+
+fn main() {
+    let builder: ClientBuilder = ClientBuilder(Tracked::new(String::new()));
+    builder.endpoint("https://api.example.com").build();
+}
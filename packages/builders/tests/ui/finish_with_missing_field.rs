@@ -0,0 +1,10 @@
+use sugars_builders::missing;
+use sugars_builders::state::Tracked;
+
+struct Endpoint;
+struct Auth;
+
+fn main() {
+    let draft: Tracked<String, missing![Endpoint, Auth]> = Tracked::new(String::new());
+    draft.set::<Endpoint, _>(|draft| draft.push_str("url")).finish();
+}
@@ -0,0 +1,11 @@
+use sugars_builders::missing;
+use sugars_builders::state::Tracked;
+
+struct Endpoint;
+struct Auth;
+struct Timeout;
+
+fn main() {
+    let draft: Tracked<u64, missing![Endpoint, Auth]> = Tracked::new(0);
+    draft.set::<Timeout, _>(|draft| *draft = 30);
+}
@@ -1,11 +1,16 @@
 //! Byte size utilities for semantic sizing
 
-#[cfg(feature = "array-tuples")]
-use serde::{Deserialize, Serialize};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 
 /// Represents a size in bytes with semantic constructors
+///
+/// Serializes as a plain byte count. Deserializes from a byte count or from
+/// a human readable string such as `"512"`, `"64KB"` or `"1.5 GiB"`; units
+/// are binary (`1KB` is 1024 bytes), matching [`ByteSize::kilobytes`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-#[cfg_attr(feature = "array-tuples", derive(Serialize, Deserialize))]
 pub struct ByteSize(pub usize);
 
 impl ByteSize {
@@ -50,6 +55,102 @@ impl std::ops::Add for ByteSize {
     }
 }
 
+/// Error returned when a string is not a valid [`ByteSize`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseByteSizeError(String);
+
+impl fmt::Display for ParseByteSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid byte size `{}`, expected a number with an optional B/KB/MB/GB/TB unit",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseByteSizeError {}
+
+impl FromStr for ByteSize {
+    type Err = ParseByteSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseByteSizeError(s.to_string());
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1 << 10,
+            "m" | "mb" | "mib" => 1 << 20,
+            "g" | "gb" | "gib" => 1 << 30,
+            "t" | "tb" | "tib" => 1 << 40,
+            _ => return Err(error()),
+        };
+        if let Ok(whole) = number.parse::<u64>() {
+            return whole
+                .checked_mul(multiplier)
+                .and_then(|bytes| usize::try_from(bytes).ok())
+                .map(ByteSize)
+                .ok_or_else(error);
+        }
+        let fractional: f64 = number.parse().map_err(|_| error())?;
+        let bytes = fractional * multiplier as f64;
+        if bytes.is_finite() && bytes >= 0.0 && bytes <= usize::MAX as f64 {
+            Ok(ByteSize(bytes.round() as usize))
+        } else {
+            Err(error())
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(usize, &str); 4] =
+            [(1 << 30, "GB"), (1 << 20, "MB"), (1 << 10, "KB"), (1, "B")];
+        for (size, unit) in UNITS {
+            if self.0 >= size && self.0.is_multiple_of(size) {
+                return write!(f, "{}{unit}", self.0 / size);
+            }
+        }
+        write!(f, "{}B", self.0)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_u64(self.0 as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        struct V;
+        impl Visitor<'_> for V {
+            type Value = ByteSize;
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a byte count or a size string like \"64KB\"")
+            }
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<ByteSize, E> {
+                usize::try_from(v)
+                    .map(ByteSize)
+                    .map_err(|_| E::custom("byte size does not fit in usize"))
+            }
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<ByteSize, E> {
+                u64::try_from(v)
+                    .map_err(|_| E::custom("byte size cannot be negative"))
+                    .and_then(|v| self.visit_u64(v))
+            }
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ByteSize, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+        de.deserialize_any(V)
+    }
+}
+
 /// Extension trait to add .bytes() method to integers
 pub trait ByteSizeExt {
     /// Converts this value to a ByteSize representing the given number of bytes.
@@ -187,6 +288,23 @@ mod tests {
         assert_eq!(i32::MAX.bytes(), ByteSize(i32::MAX as usize));
     }
 
+    #[test]
+    fn test_byte_size_from_str() {
+        assert_eq!("512".parse(), Ok(ByteSize(512)));
+        assert_eq!("64KB".parse(), Ok(ByteSize::kilobytes(64)));
+        assert_eq!(" 1.5 MiB ".parse(), Ok(ByteSize(1024 * 1024 * 3 / 2)));
+        assert!("ten megs".parse::<ByteSize>().is_err());
+        assert_eq!(ByteSize::megabytes(10).to_string(), "10MB");
+    }
+
+    #[test]
+    fn test_byte_size_serde() {
+        let size: ByteSize = serde_json::from_str("\"2KB\"").unwrap();
+        assert_eq!(size, ByteSize(2048));
+        let size: ByteSize = serde_json::from_str("2048").unwrap();
+        assert_eq!(serde_json::to_string(&size).unwrap(), "2048");
+    }
+
     #[test]
     fn test_from_usize() {
         let size = ByteSize::from(2048);
@@ -5,6 +5,9 @@
 pub mod byte_size;
 /// A non-empty collection guaranteed to hold at least one value.
 pub mod one_or_many;
+/// JSON Schema impls for the collection types.
+#[cfg(feature = "schemars")]
+pub mod schema;
 /// A collection that can hold zero, one, or many values, optimized for minimal allocations.
 pub mod zero_one_or_many;
 
@@ -12,7 +15,7 @@ pub mod zero_one_or_many;
 pub mod array_tuple_ext;
 
 // Re-export main types
-pub use byte_size::{ByteSize, ByteSizeExt};
+pub use byte_size::{ByteSize, ByteSizeExt, ParseByteSizeError};
 pub use one_or_many::OneOrMany;
 pub use zero_one_or_many::ZeroOneOrMany;
 
@@ -2,7 +2,7 @@
 // src/one_or_many.rs
 // -----------------------------------------------------------------------------
 
-use super::zero_one_or_many::ZeroOneOrMany;
+use super::zero_one_or_many::{ZeroOneOrMany, visit_single};
 use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{SerializeSeq, Serializer};
 use serde::{Deserialize, Serialize};
@@ -292,6 +292,8 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for OneOrMany<T> {
                 let v = Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))?;
                 Ok(OneOrMany(ZeroOneOrMany::One(v)))
             }
+
+            visit_single!(|v| OneOrMany(ZeroOneOrMany::One(v)));
         }
 
         de.deserialize_any(V(PhantomData))
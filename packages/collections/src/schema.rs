@@ -0,0 +1,140 @@
+//! JSON Schema support for the collection types, via `schemars`
+//!
+//! The schemas describe what the types accept when deserialized:
+//!
+//! - `OneOrMany<T>`: a single `T` or a non-empty array of `T`
+//! - `ZeroOneOrMany<T>`: `null`, a single `T` or an array of `T`
+//! - `ByteSize`: a byte count, or a string such as `"10MB"` or `"1.5 GiB"`
+
+use crate::{ByteSize, OneOrMany, ZeroOneOrMany};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use std::borrow::Cow;
+
+/// Strings accepted by `ByteSize::from_str`
+pub const BYTE_SIZE_PATTERN: &str = r"^\s*[0-9]+(\.[0-9]+)?\s*([KkMmGgTt]([Ii]?[Bb])?|[Bb])?\s*$";
+
+impl<T: JsonSchema> JsonSchema for OneOrMany<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("OneOrMany_for_{}", T::schema_name()).into()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        format!("sugars_collections::OneOrMany<{}>", T::schema_id()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let item = generator.subschema_for::<T>();
+        json_schema!({
+            "anyOf": [
+                item,
+                {
+                    "type": "array",
+                    "items": item,
+                    "minItems": 1
+                }
+            ]
+        })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for ZeroOneOrMany<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("ZeroOneOrMany_for_{}", T::schema_name()).into()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        format!("sugars_collections::ZeroOneOrMany<{}>", T::schema_id()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let item = generator.subschema_for::<T>();
+        json_schema!({
+            "anyOf": [
+                { "type": "null" },
+                item,
+                {
+                    "type": "array",
+                    "items": item
+                }
+            ]
+        })
+    }
+}
+
+impl JsonSchema for ByteSize {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "ByteSize".into()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        "sugars_collections::ByteSize".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [
+                {
+                    "type": "integer",
+                    "format": "uint64",
+                    "minimum": 0
+                },
+                {
+                    "type": "string",
+                    "pattern": BYTE_SIZE_PATTERN
+                }
+            ]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::schema_for;
+    use serde_json::json;
+
+    #[test]
+    fn one_or_many_requires_an_item() {
+        let schema = schema_for!(OneOrMany<String>);
+        assert_eq!(
+            schema.get("anyOf"),
+            Some(&json!([
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+            ]))
+        );
+    }
+
+    #[test]
+    fn byte_size_pattern_matches_the_parser() {
+        let schema = schema_for!(ByteSize);
+        assert_eq!(
+            schema.get("anyOf").unwrap()[1]["pattern"],
+            BYTE_SIZE_PATTERN
+        );
+
+        for accepted in ["10", "10MB", "1.5 GiB", "512k", "2b"] {
+            assert!(accepted.parse::<ByteSize>().is_ok(), "{accepted}");
+        }
+    }
+
+    #[test]
+    fn scalars_deserialize_as_a_single_item() {
+        let value: ZeroOneOrMany<String> = serde_json::from_value(json!("solo")).unwrap();
+        assert_eq!(value, ZeroOneOrMany::one("solo".to_string()));
+        let value: OneOrMany<u32> = serde_json::from_value(json!(7)).unwrap();
+        assert_eq!(value, OneOrMany::one(7));
+    }
+}
@@ -300,6 +300,31 @@ impl<'a, T> IntoIterator for &'a ZeroOneOrMany<T> {
 }
 
 // Serde Support
+
+/// Visitor methods that deserialize a bare scalar as a single element, so
+/// `"a"` reads the same as `["a"]`
+macro_rules! visit_single {
+    ($wrap:expr) => {
+        visit_single!(@one visit_bool, bool, BoolDeserializer, $wrap);
+        visit_single!(@one visit_i64, i64, I64Deserializer, $wrap);
+        visit_single!(@one visit_u64, u64, U64Deserializer, $wrap);
+        visit_single!(@one visit_f64, f64, F64Deserializer, $wrap);
+        visit_single!(@one visit_str, &str, StrDeserializer, $wrap);
+        visit_single!(@one visit_string, String, StringDeserializer, $wrap);
+    };
+    (@one $method:ident, $ty:ty, $deserializer:ident, $wrap:expr) => {
+        #[inline]
+        fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let v = Deserialize::deserialize(de::value::$deserializer::<E>::new(v))?;
+            Ok($wrap(v))
+        }
+    };
+}
+pub(crate) use visit_single;
+
 impl<T: Serialize> Serialize for ZeroOneOrMany<T> {
     fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
         match self {
@@ -368,6 +393,8 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ZeroOneOrMany<T> {
                 let v = Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))?;
                 Ok(ZeroOneOrMany::One(v))
             }
+
+            visit_single!(ZeroOneOrMany::One);
         }
 
         de.deserialize_any(V(PhantomData))
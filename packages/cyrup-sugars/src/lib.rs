@@ -89,7 +89,8 @@ pub use sugars_collections::{
 
 // Re-export async utilities
 pub use r#async::{
-    AsyncResult, AsyncResultChunk, AsyncStream, AsyncTask, FutureExt, NotResult, StreamExt,
+    AsyncResult, AsyncResultChunk, AsyncStream, AsyncTask, FutureExt, IntoResult, NotResult,
+    StreamExt,
 };
 
 // Re-export JSON syntax macros for array-tuples feature
@@ -111,10 +112,10 @@ pub mod prelude {
     pub use crate::{hash_map, hash_map_fn};
 
     // Re-export async utilities
-    pub use crate::r#async::{FutureExt, NotResult, StreamExt};
+    pub use crate::r#async::{FutureExt, IntoResult, NotResult, StreamExt};
 
     // Re-export builder utilities
-    pub use crate::builders::{ChunkHandler, MessageChunk};
+    pub use crate::builders::{ChunkChain, ChunkHandler, ChunkStreamExt, MessageChunk};
 
     // Re-export macros for elegant stream processing (from local closures module)
     pub use crate::on_result;
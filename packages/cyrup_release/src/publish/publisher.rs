@@ -9,6 +9,8 @@ use crate::workspace::{WorkspaceInfo, DependencyGraph, PublishOrder, PublishTier
 use semver::Version;
 use std::collections::HashMap;
 use std::time::Duration;
+use sugars_async_stream::Progress;
+use sugars_async_task::{ConcurrencyLimiter, Limiter, RateLimiter};
 use tokio::time::sleep;
 
 /// Publishing orchestrator for workspace packages
@@ -24,12 +26,19 @@ pub struct Publisher {
     config: PublisherConfig,
     /// State of current publishing operation
     publish_state: PublishState,
+    /// Live progress, one unit per package attempted
+    progress: Progress,
+    /// Spaces publishes out by `inter_package_delay`
+    rate: RateLimiter,
+    /// Caps the publishes running at once within a tier
+    concurrency: ConcurrencyLimiter,
 }
 
 /// Configuration for the publishing orchestrator
 #[derive(Debug, Clone)]
 pub struct PublisherConfig {
-    /// Delay between publishing packages (user requested 15 seconds)
+    /// Minimum time between the starts of two publishes, and the wait after
+    /// each tier finishes (user requested 15 seconds)
     pub inter_package_delay: Duration,
     /// Whether to perform dry run validation first
     pub dry_run_first: bool,
@@ -121,6 +130,7 @@ impl Publisher {
         let cargo_publisher = CargoPublisher::new();
         let config = PublisherConfig::default();
         let publish_state = PublishState::default();
+        let (rate, concurrency) = Self::limiters(&config);
 
         Ok(Self {
             workspace: workspace.clone(),
@@ -128,6 +138,9 @@ impl Publisher {
             cargo_publisher,
             config,
             publish_state,
+            progress: Progress::new(),
+            rate,
+            concurrency,
         })
     }
 
@@ -136,6 +149,7 @@ impl Publisher {
         let dependency_graph = DependencyGraph::build(workspace)?;
         let cargo_publisher = CargoPublisher::new();
         let publish_state = PublishState::default();
+        let (rate, concurrency) = Self::limiters(&config);
 
         Ok(Self {
             workspace: workspace.clone(),
@@ -143,6 +157,9 @@ impl Publisher {
             cargo_publisher,
             config,
             publish_state,
+            progress: Progress::new(),
+            rate,
+            concurrency,
         })
     }
 
@@ -153,6 +170,7 @@ impl Publisher {
         // Get publishing order
         let publish_order = self.dependency_graph.publish_order()?;
         self.publish_state.total_tiers = publish_order.tier_count();
+        self.progress.reset(Some(publish_order.total_packages as u64));
 
         // Perform dry run validation if requested
         if self.config.dry_run_first {
@@ -162,10 +180,15 @@ impl Publisher {
         // Publish packages tier by tier
         for (tier_index, tier) in publish_order.tiers.iter().enumerate() {
             self.publish_state.current_tier = tier_index;
+            self.progress.set_message(format!(
+                "Publishing tier {}/{}",
+                tier_index + 1,
+                publish_order.tier_count()
+            ));
             
             match self.publish_tier(tier).await {
                 Ok(()) => {
-                    // Add delay between tiers (except after the last tier)
+                    // Let the registry settle before the next tier depends on this one
                     if tier_index < publish_order.tiers.len() - 1 {
                         sleep(self.config.inter_package_delay).await;
                     }
@@ -176,11 +199,14 @@ impl Publisher {
                 }
                 Err(e) => {
                     // Fail fast - stop publishing
+                    self.progress.finish();
                     return Err(e);
                 }
             }
         }
 
+        self.progress.finish();
+
         let total_duration = self.publish_state.start_time
             .map(|start| start.elapsed())
             .unwrap_or_default();
@@ -244,16 +270,19 @@ impl Publisher {
     ) -> Result<()> {
         let package_info = self.workspace.get_package(package_name)?;
         
+        self.rate.acquire().await;
         println!("📦 Publishing {} v{}...", package_name, package_info.version);
         
         match self.cargo_publisher.publish_package(package_info, publish_config).await {
             Ok(result) => {
                 println!("✅ {}", result.summary());
+                self.progress.step(format!("Published {}", package_name));
                 self.publish_state.completed_publishes.insert(package_name.to_string(), result);
                 Ok(())
             }
             Err(e) => {
                 let error_msg = format!("Failed to publish {}: {}", package_name, e);
+                self.progress.step(error_msg.clone());
                 self.publish_state.failed_packages.insert(package_name.to_string(), error_msg.clone());
                 Err(PublishError::PublishFailed {
                     package: package_name.to_string(),
@@ -264,64 +293,96 @@ impl Publisher {
     }
 
     /// Publish multiple packages concurrently (within a tier)
+    ///
+    /// Packages run in a task scope. Unless `continue_on_failure` is set, the
+    /// first failure stops packages that have not started yet, but publishes
+    /// already running are left to finish: dropping them would leave their
+    /// `cargo publish` running unrecorded, out of reach of rollback. Every
+    /// publish that finished is recorded before the failure is returned.
     async fn publish_packages_concurrently(
         &mut self,
         package_names: &[String],
         publish_config: &PublishConfig,
     ) -> Result<()> {
-        use tokio::sync::Semaphore;
+        use tokio::sync::mpsc;
         use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use sugars_async_task::ScopeError;
         
-        let semaphore = Arc::new(Semaphore::new(self.config.max_concurrent_per_tier));
-        let mut handles = Vec::new();
+        let continue_on_failure = self.config.continue_on_failure;
+        let halted = Arc::new(AtomicBool::new(false));
+        let (finished_tx, mut finished_rx) = mpsc::unbounded_channel();
 
+        let mut packages = Vec::with_capacity(package_names.len());
         for package_name in package_names {
-            let package_info = self.workspace.get_package(package_name)?.clone();
-            let publisher = self.cargo_publisher.clone();
-            let config = publish_config.clone();
-            let semaphore = Arc::clone(&semaphore);
-            let package_name = package_name.clone();
-
-            let handle = tokio::spawn(async move {
-                let _permit = semaphore.acquire().await.unwrap();
-                
-                println!("📦 Publishing {} v{}...", package_name, package_info.version);
-                
-                let result = publisher.publish_package(&package_info, &config).await;
-                (package_name, result)
-            });
-
-            handles.push(handle);
+            packages.push((package_name.clone(), self.workspace.get_package(package_name)?.clone()));
         }
 
-        // Wait for all publishes to complete
-        for handle in handles {
-            let (package_name, result) = handle.await
-                .map_err(|e| PublishError::PublishFailed {
-                    package: "unknown".to_string(),
-                    reason: format!("Task join error: {}", e),
-                })?;
+        let publisher = self.cargo_publisher.clone();
+        let config = publish_config.clone();
+        let rate = self.rate.clone();
+        let concurrency = self.concurrency.clone();
+        let outcome = sugars_async_task::scope(|s| async move {
+            for (package_name, package_info) in packages {
+                let publisher = publisher.clone();
+                let config = config.clone();
+                let rate = rate.clone();
+                let concurrency = concurrency.clone();
+                let finished = finished_tx.clone();
+                let halted = Arc::clone(&halted);
+
+                s.spawn(async move {
+                    let _permit = concurrency.acquire().await;
+                    rate.acquire().await;
+                    if halted.load(Ordering::Acquire) {
+                        return Ok::<_, PublishError>(());
+                    }
+                    
+                    println!("📦 Publishing {} v{}...", package_name, package_info.version);
+                    
+                    let result = publisher.publish_package(&package_info, &config).await;
+                    if result.is_err() && !continue_on_failure {
+                        halted.store(true, Ordering::Release);
+                    }
+                    let _ = finished.send((package_name, result));
+                    Ok(())
+                });
+            }
+        })
+        .await;
 
+        // Record every publish that finished, returning the first failure
+        // once all of them are recorded
+        let mut failure = None;
+        while let Ok((package_name, result)) = finished_rx.try_recv() {
             match result {
                 Ok(publish_result) => {
                     println!("✅ {}", publish_result.summary());
+                    self.progress.step(format!("Published {}", package_name));
                     self.publish_state.completed_publishes.insert(package_name, publish_result);
                 }
                 Err(e) => {
                     let error_msg = format!("Failed to publish {}: {}", package_name, e);
-                    self.publish_state.failed_packages.insert(package_name.clone(), error_msg.clone());
-                    
-                    if !self.config.continue_on_failure {
-                        return Err(PublishError::PublishFailed {
-                            package: package_name,
-                            reason: error_msg,
-                        }.into());
+                    self.progress.step(error_msg.clone());
+                    if !continue_on_failure && failure.is_none() {
+                        failure = Some(PublishError::PublishFailed {
+                            package: package_name.clone(),
+                            reason: error_msg.clone(),
+                        });
                     }
+                    self.publish_state.failed_packages.insert(package_name, error_msg);
                 }
             }
         }
 
-        Ok(())
+        match outcome {
+            Ok(_) => failure.map_or(Ok(()), |e| Err(e.into())),
+            Err(ScopeError::Failed(e)) => Err(e.into()),
+            Err(ScopeError::Panicked(message)) => Err(PublishError::PublishFailed {
+                package: "unknown".to_string(),
+                reason: format!("Publish task panicked: {}", message),
+            }.into()),
+        }
     }
 
     /// Rollback published packages by yanking them
@@ -416,11 +477,28 @@ impl Publisher {
         }
     }
 
+    /// Live progress handle for publish runs
+    ///
+    /// Watch it with `Progress::watch` or `Progress::events` before calling
+    /// `publish_all_packages`; each attempted package counts as one unit.
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
     /// Update publisher configuration
     pub fn set_config(&mut self, config: PublisherConfig) {
+        (self.rate, self.concurrency) = Self::limiters(&config);
         self.config = config;
     }
 
+    /// Limiters enforcing `inter_package_delay` and `max_concurrent_per_tier`
+    fn limiters(config: &PublisherConfig) -> (RateLimiter, ConcurrencyLimiter) {
+        (
+            RateLimiter::new(1, config.inter_package_delay),
+            ConcurrencyLimiter::new(config.max_concurrent_per_tier),
+        )
+    }
+
     /// Get publisher configuration
     pub fn config(&self) -> &PublisherConfig {
         &self.config
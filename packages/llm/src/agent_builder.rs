@@ -241,6 +241,10 @@ impl MessageChunk for ConversationChunk {
     fn error(&self) -> Option<&str> {
         self.error.as_deref()
     }
+
+    fn text(&self) -> Option<&str> {
+        Some(&self.content)
+    }
 }
 
 impl std::fmt::Display for ConversationChunk {